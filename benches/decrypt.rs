@@ -0,0 +1,64 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rejson::{Key, KeyPair, SecretsFile};
+use serde_json::{Map, Value, json};
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+/// Returns an encrypted file with `n` values. When `shared` is true, every value is encrypted in
+/// the same run (like `rejson encrypt`), otherwise each value gets its own ephemeral key (like
+/// adding values one at a time with `rejson set`).
+fn secrets_file(keys: &KeyPair, n: usize, shared: bool) -> SecretsFile {
+    let values = (0..n)
+        .map(|i| (format!("secret_{}", i), Value::from(format!("value {}", i))))
+        .collect::<Map<_, _>>();
+
+    let mut file: SecretsFile = json!({ "_public_key": keys.public_key(), "secrets": values })
+        .to_string()
+        .parse()
+        .unwrap();
+
+    match shared {
+        true => file.transform(rejson::encrypt(&file).unwrap()).unwrap(),
+        false => (0..n).for_each(|i| {
+            let key = format!("secrets.secret_{}", i);
+            let value = rejson::encrypt(&file).unwrap()(format!("value {}", i)).unwrap();
+            file.set(&key, value.into(), false).unwrap();
+        }),
+    }
+
+    file
+}
+
+fn decrypt(c: &mut Criterion) {
+    let keys = KeyPair::generate().unwrap();
+    let private_key: Key = keys.private_key().parse().unwrap();
+    let mut group = c.benchmark_group("decrypt");
+    group.sample_size(10);
+
+    for n in SIZES {
+        group.throughput(Throughput::Elements(n as u64));
+
+        for (name, shared) in [("shared_ephemeral_key", true), ("unique_ephemeral_keys", false)] {
+            let contents = secrets_file(&keys, n, shared).to_string();
+
+            group.bench_function(BenchmarkId::new(name, n), |b| {
+                b.iter_batched(
+                    || contents.parse::<SecretsFile>().unwrap(),
+                    |mut file| {
+                        file.transform(rejson::decrypt(&file, private_key.clone()).unwrap())
+                            .unwrap();
+                        black_box(file)
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, decrypt);
+criterion_main!(benches);
@@ -0,0 +1,2479 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{IsTerminal, Read, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        Mutex,
+        OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use rejson::{
+    self,
+    Charset,
+    CheckReport,
+    Config,
+    Diagnostic,
+    Generator,
+    Inventory,
+    Key,
+    KeyPair,
+    KeySource,
+    KrmConfig,
+    ResourceList,
+    SecretsDiff,
+    SecretsDir,
+    SecretsFile,
+    SecretsManifest,
+    SecretsMap,
+    Severity,
+};
+use report::{Failure, FileStatus, Report};
+use serde_json::json;
+
+mod report;
+
+/// The default place to find private keys.
+const DEFAULT_KEYDIR: &str = "/opt/ejson/keys";
+
+/// The files included when walking directories.
+const DEFAULT_INCLUDE: &str = "*.ejson";
+
+/// The characters that make a path a glob pattern.
+const GLOB_CHARS: [char; 4] = ['*', '?', '[', '{'];
+
+/// The name of the git diff and merge drivers configured by `git setup`.
+const GIT_DRIVER: &str = "ejson";
+
+/// The project configuration, discovered from the current directory when the command starts.
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The exit statuses, listed in the help.
+const EXIT_CODES: &str = "Exit codes:
+  0  Success
+  1  Any other failure (e.g. unreadable files, differences found by diff, merge conflicts)
+  2  Usage error (invalid arguments)
+  3  The private key couldn't be found or read, or the file's _public_key is missing or invalid
+  4  A value couldn't be decrypted
+  5  Validation failed (check found problems, or a secret would be rejected by Kubernetes)";
+
+#[derive(Parser)]
+#[command(author, about, version, after_long_help = EXIT_CODES)]
+struct Cli {
+    /// The output format [default: human]. With json, nothing is printed until the command finishes, and then a
+    /// single JSON object with the files written, key fingerprints used, warnings, errors and the command's result is
+    /// printed to stdout.
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Encrypt one or more EJSON files.
+    ///
+    /// Files are encrypted in parallel, and a failure in one file doesn't stop the others unless --fail-fast is
+    /// supplied. Exits with a non-zero status when any file fails.
+    #[command(alias = "e")]
+    Encrypt {
+        /// The file(s), directories (with --recursive) and/or glob patterns (e.g. 'services/**/*.ejson') to encrypt.
+        #[arg(num_args = 1.., value_parser)]
+        file: Vec<String>,
+
+        /// Encrypt the files in directories and their subdirectories, skipping hidden and git ignored files.
+        #[arg(short, long)]
+        recursive: bool,
+
+        #[command(flatten)]
+        filter: FileFilter,
+
+        /// Stop at the first file that fails.
+        #[arg(long)]
+        fail_fast: bool,
+    },
+
+    /// Decrypt an EJSON file.
+    ///
+    /// Decrypt the given file; that is, decrypt all the encrypted keys within it, printing the full decrypted file.
+    /// The key mentioned in the ejson file must be present in the keydir.
+    #[command(alias = "d")]
+    Decrypt {
+        /// The file to decrypt.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+
+        /// If given, write the decrypted file to FILE rather than stdout.
+        #[arg(short, long)]
+        out: Option<String>,
+
+        /// Omit the _public_key from the result.
+        #[arg(short, long)]
+        strip_key: bool,
+    },
+
+    /// Check EJSON files for values that aren't encrypted, without needing the private key.
+    ///
+    /// Fails when a value that should be encrypted isn't, an encrypted value is malformed or has an unknown version,
+    /// the _public_key is missing or invalid, or a value that's skipped by encryption (its key starts with _) looks
+    /// like a token. Directories are searched recursively for *.ejson files, skipping hidden and git ignored files.
+    Check {
+        /// The files, directories and/or glob patterns (e.g. 'services/**/*.ejson') to check.
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+
+        /// The output format [default: human].
+        #[arg(short, long, value_enum)]
+        format: Option<CheckFormat>,
+
+        #[command(flatten)]
+        filter: FileFilter,
+
+        /// Stop at the first file with problems.
+        #[arg(long)]
+        fail_fast: bool,
+    },
+
+    /// Verify that every encrypted value in an EJSON file can be decrypted with the available key.
+    ///
+    /// Reports the path of every value that fails to decrypt (without printing any plaintexts), as well as values
+    /// whose plaintext isn't valid UTF-8 and values encrypted with a different ephemeral key than the rest of the file.
+    /// Exits with a non-zero status when any value fails to decrypt.
+    Verify {
+        /// The file to verify.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+    },
+
+    /// Print a single decrypted value.
+    ///
+    /// The key uses dot-notation (e.g. database.password), with keys containing dots surrounded by square brackets
+    /// (e.g. files.[config.json]). Only the requested value is decrypted. Objects are printed as JSON.
+    Get {
+        /// The file to read from.
+        file: String,
+
+        /// The key of the value.
+        key: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+    },
+
+    /// Set a single value, encrypting it with the file's public key.
+    ///
+    /// The value is read from stdin, or prompted for when stdin is a terminal. The key uses the same dot-notation as
+    /// get. No private key is required, and the rest of the file is left as is.
+    Set {
+        /// The file to update.
+        file: String,
+
+        /// The key of the value.
+        key: String,
+
+        /// Create any missing parent objects.
+        #[arg(long)]
+        create: bool,
+
+        /// Parse the value as JSON, allowing objects, numbers, etc. to be set.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the values that expire soon, from the _meta objects in EJSON files.
+    ///
+    /// Values opt in to metadata with an entry in a _meta object next to them (e.g. database._meta.password), which
+    /// may have a description, owner, rotated_at and expires_at. Values that expired already are included.
+    /// Directories are searched recursively for *.ejson files. No private key is required.
+    Expiring {
+        /// The files and/or directories to check.
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+
+        /// List values expiring within this long (e.g. 12h, 30d or 4w).
+        #[arg(long, default_value = "30d", value_parser = parse_duration)]
+        within: Duration,
+
+        /// The output format [default: human].
+        #[arg(short, long, value_enum)]
+        format: Option<OutputFormat>,
+    },
+
+    /// List every secret in a tree of EJSON files, without their values.
+    ///
+    /// Each secret is listed with its file, key, encryption status, message version and any _meta description and
+    /// owner, grouped by the file's public key (and its fingerprint). Values are never decrypted or printed, so no
+    /// private key is required. Directories are searched recursively for *.ejson files.
+    Inventory {
+        /// The files and/or directories to list.
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+
+        /// The output format [default: markdown].
+        #[arg(short, long, value_enum)]
+        format: Option<InventoryFormat>,
+    },
+
+    /// Diagnose problems with the keydir and the private keys of EJSON files.
+    ///
+    /// Inspects every key file in the keydir, checking that it's only accessible to its owner, contains nothing but a
+    /// hex encoded private key, and is named after the public key derived from that private key. Each file's
+    /// _public_key is then checked against the usable keys where the file's key is looked up (the keydir of a matching
+    /// rule, key_env or the keydir). Problems are listed with a suggested fix, and private keys are never printed.
+    /// Directories are searched recursively for *.ejson files.
+    Doctor {
+        /// The files and/or directories to check the keys of.
+        paths: Vec<String>,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+    },
+
+    /// Generate a random value and store it, encrypted, in a file.
+    ///
+    /// No private key is required, and the value is only printed when --show is supplied. The length is the number
+    /// of characters for alnum, the number of random bytes (before encoding) for hex and base64, and the number of
+    /// (dot separated) words for words. Ed25519 key pairs are stored as an object with an (unencrypted) _public_key and
+    /// a private_key.
+    Generate {
+        /// The file to update.
+        file: String,
+
+        /// The key of the value.
+        key: String,
+
+        /// The kind of value to generate.
+        #[arg(long, value_enum, default_value_t = GenerateKind::Random)]
+        kind: GenerateKind,
+
+        /// The characters used for random values.
+        #[arg(long, value_enum, default_value_t = GenerateCharset::Alnum)]
+        charset: GenerateCharset,
+
+        /// The length of random values [default: 32, or 6 for words].
+        #[arg(long)]
+        length: Option<usize>,
+
+        /// Create any missing parent objects.
+        #[arg(long)]
+        create: bool,
+
+        /// Do nothing when the key already exists.
+        #[arg(long, conflicts_with = "force")]
+        if_missing: bool,
+
+        /// Overwrite an existing value.
+        #[arg(long)]
+        force: bool,
+
+        /// Print the generated value.
+        #[arg(long)]
+        show: bool,
+    },
+
+    /// List the keys in a file, and whether each value is encrypted.
+    ///
+    /// Keys are printed in the same dot-notation as get. No private key is required.
+    Ls {
+        /// The file to list.
+        file: String,
+
+        /// Only list the keys under this key.
+        key: Option<String>,
+    },
+
+    /// Remove a value (or object) from a file.
+    ///
+    /// No private key is required, and the rest of the file is left as is.
+    Rm {
+        /// The file to update.
+        file: String,
+
+        /// The key of the value.
+        key: String,
+    },
+
+    /// Move (rename) a value within a file.
+    ///
+    /// No private key is required, and the rest of the file is left as is. Plaintext values moved to a key that's
+    /// encrypted are encrypted.
+    Mv {
+        /// The file to update.
+        file: String,
+
+        /// The key of the value to move.
+        from: String,
+
+        /// The new key of the value.
+        to: String,
+
+        /// Create any missing parent objects.
+        #[arg(long)]
+        create: bool,
+
+        /// Overwrite an existing value.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Copy a value within a file, or to another file.
+    ///
+    /// Encrypted values are copied as is, unless the destination file has a different public key, in which case
+    /// they're decrypted (requiring the source file's private key) and re-encrypted with the destination's key.
+    Cp {
+        /// The file to copy from.
+        file: String,
+
+        /// The key of the value to copy.
+        from: String,
+
+        /// The key to copy the value to.
+        to: String,
+
+        /// The file to copy the value to (defaults to the same file).
+        #[arg(long)]
+        to_file: Option<String>,
+
+        /// Create any missing parent objects.
+        #[arg(long)]
+        create: bool,
+
+        /// Overwrite an existing value.
+        #[arg(long)]
+        force: bool,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+    },
+
+    /// Show the keys and values that differ between two EJSON files.
+    ///
+    /// Either file can be read from git by supplying it as FILE@REV (e.g. secrets.ejson@main). Files are decrypted
+    /// (when their private keys are available) so values can be compared even when the files use different keys.
+    /// Values are masked by a hash that's only valid for this diff unless --show-values is supplied. Exits with a
+    /// non-zero status when the files differ.
+    Diff {
+        /// The file to compare from (FILE or FILE@REV).
+        from: String,
+
+        /// The file to compare to (FILE or FILE@REV).
+        to: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Show the (decrypted) values rather than masking them.
+        #[arg(long)]
+        show_values: bool,
+    },
+
+    /// Generate a new EJSON key pair.
+    #[command(alias = "g")]
+    Keygen {
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Write the private key to the key dir.
+        #[arg(short, long)]
+        write: bool,
+    },
+
+    /// Create a new EJSON file, generating a key pair for it.
+    ///
+    /// The private key is written to the keydir with mode 0600 and the file is created with its _public_key (and
+    /// optionally empty sections for env and kube-secrets). Existing files and keys are never overwritten. New files
+    /// get the public key from --public-key or a matching rule in .rejson.toml instead of a new key pair when either is
+    /// set.
+    Init {
+        /// The file to create.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Use this (existing) public key rather than generating a key pair.
+        #[arg(long)]
+        public_key: Option<String>,
+
+        /// Add an empty section for the values exported by env.
+        #[arg(long)]
+        env: bool,
+
+        /// Add an empty section for the secrets generated by kube-secrets.
+        #[arg(long)]
+        kube_secrets: bool,
+    },
+
+    /// Export the all values under the "environment" key.
+    Env {
+        /// The file to decrypt.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+
+        /// The path to write the export statements to.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+
+    /// Generate a K8s manifest for secrets defined under the "kubernetes" key.
+    ///
+    /// The expected format for this key is as follows:
+    ///
+    /// ```json
+    /// {
+    ///   "_public_key": "...",
+    ///   ...
+    ///   "kubernetes": {
+    ///     "secret_name": {
+    ///       "KEY": "EJ[1:...]",
+    ///       "OTHER_KEY": "EJ[1:...]"
+    ///     },
+    ///     ...
+    ///     ...
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// The output would be a manifest will something like the following (one for each secret
+    /// separated by `---`)
+    ///
+    /// ```yaml
+    /// api: v1
+    /// kind: Secret
+    /// metadata:
+    ///   name: secret_name
+    /// data:
+    ///   KEY: <base64 decrypted value>
+    ///   OTHER_KEY: <base64 decrypted value>
+    /// ```
+    ///
+    /// You can optionally add `"_namespace": "my-ns"` to any secret to have it be defined in the
+    /// my-ns namespace. To define the same secret in multiple namespaces, set `"_namespaces": true` and nest
+    /// secrets under their namespace instead (e.g. `"kubernetes": { "_namespaces": true, "my-ns": { ... } }`).
+    /// Similarly, `"_type"` sets the secret type, `"_labels"` and `"_annotations"` objects add metadata, and
+    /// `"_immutable": true` marks the secret as immutable.
+    KubeSecrets {
+        /// The file to decrypt.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+
+        /// The path to write the manifest to.
+        #[arg(short, long, conflicts_with = "out_dir")]
+        out: Option<String>,
+
+        /// Write each secret to its own file (<namespace>/<name>.yaml) under this directory.
+        ///
+        /// Files generated from the same source file on an earlier run whose secrets no longer exist are removed,
+        /// and files that are already up to date are not rewritten.
+        #[arg(long)]
+        out_dir: Option<String>,
+
+        /// Also write a kustomization.yaml listing the generated files (requires --out-dir).
+        #[arg(long, requires = "out_dir")]
+        kustomization: bool,
+
+        /// Compare the secrets to an existing manifest (a YAML file or a directory of them) instead of writing them.
+        ///
+        /// Reports the added, removed and changed keys of each secret along with a short hash of each value, keyed
+        /// with a random key for each run (values are never printed), and exits with a non-zero status when
+        /// there are differences.
+        #[arg(long, value_name = "MANIFEST", conflicts_with_all = ["out", "out_dir"])]
+        diff: Option<String>,
+
+        /// A label (KEY=VALUE) to add to every secret. Can be supplied multiple times.
+        #[arg(short, long = "label", value_parser = parse_key_value)]
+        labels: Vec<(String, String)>,
+
+        /// An annotation (KEY=VALUE) to add to every secret. Can be supplied multiple times.
+        #[arg(short, long = "annotation", value_parser = parse_key_value)]
+        annotations: Vec<(String, String)>,
+
+        /// Write plain text values under stringData rather than base64 encoded data.
+        #[arg(long)]
+        string_data: bool,
+
+        /// Override the namespace of every generated secret.
+        #[arg(short, long)]
+        namespace: Option<String>,
+
+        /// Only generate secrets defined in this namespace. Can be supplied multiple times.
+        #[arg(long)]
+        filter_namespace: Vec<String>,
+    },
+
+    /// Write each decrypted value to its own file, for daemons that read secrets from files.
+    ///
+    /// Nested objects become subdirectories and keys starting with an underscore are skipped. Files are written
+    /// atomically with mode 0400 and directories are created with mode 0700.
+    Materialize {
+        /// The file to decrypt.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+
+        /// Read the private key from stdin.
+        #[arg(long)]
+        key_from_stdin: bool,
+        /// Only write the values under this key, e.g. "environment" (in the same dot-notation as get and set).
+        /// Only write the values under this (dot-delimited) key, e.g. "environment".
+        #[arg(short, long)]
+        root: Option<String>,
+
+        /// The directory to write the files to.
+        #[arg(short, long)]
+        dir: String,
+
+        /// Set the owner of the files and directories to this (numeric) user id.
+        #[arg(long)]
+        uid: Option<u32>,
+
+        /// Set the group of the files and directories to this (numeric) group id.
+        #[arg(long)]
+        gid: Option<u32>,
+
+        /// Remove files written by an earlier run (listed in the directory's .rejson-manifest) that no longer
+        /// correspond to a key. Files rejson didn't write are left alone.
+        #[arg(long)]
+        clean: bool,
+    },
+
+    /// Print an EJSON file for git diff, decrypted when its private key is available.
+    ///
+    /// When the key can't be found, encrypted values (and any that fail to decrypt) are replaced with a short hash of
+    /// their ciphertext so it's still possible to see which values changed. Intended to be used as the textconv of a
+    /// git diff driver (see `rejson git setup`).
+    GitTextconv {
+        /// The file to print.
+        file: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+    },
+
+    /// Merge EJSON files as a git merge driver.
+    ///
+    /// Performs a structural three-way merge of the files, writing the result to OURS in its existing formatting.
+    /// Keys added, changed or removed on only one side merge cleanly. Values changed on both sides are compared by
+    /// their plaintext when the private key is available, and otherwise reported as conflicts (exiting with a
+    /// non-zero status). Intended to be configured with `rejson git setup`.
+    GitMerge {
+        /// The common ancestor's version of the file (%O).
+        base: String,
+
+        /// Our version of the file (%A), which the result is written to.
+        ours: String,
+
+        /// Their version of the file (%B).
+        theirs: String,
+
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+    },
+
+    /// Helpers for working with EJSON files in git repositories.
+    Git {
+        #[command(subcommand)]
+        command: GitCommands,
+    },
+
+    /// Show the project configuration.
+    ///
+    /// Settings are read from the .rejson.toml in the current directory or the closest of its parents. Command line
+    /// flags (and EJSON_KEYDIR) override them.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Run as a KRM function that generates secrets, for use with kustomize or kpt.
+    ///
+    /// Reads a ResourceList from stdin and writes it to stdout with the secrets generated by
+    /// kube-secrets appended to its items. The functionConfig's spec configures the function:
+    ///
+    /// ```yaml
+    /// apiVersion: rejson.pseudomuto.com/v1
+    /// kind: SecretsGenerator
+    /// metadata:
+    ///   name: secrets
+    ///   annotations:
+    ///     config.kubernetes.io/function: |
+    ///       exec:
+    ///         path: rejson
+    ///         args: ["krm"]
+    /// spec:
+    ///   files: ["secrets.ejson"]
+    ///   keydir: /opt/ejson/keys  # or keyEnv: NAME_OF_VAR_WITH_PRIVATE_KEY
+    ///   namespace: my-ns         # optional
+    ///   labels: {}               # optional
+    ///   annotations: {}          # optional
+    ///   stringData: false        # optional
+    /// ```
+    ///
+    /// Errors are reported in the results of the ResourceList.
+    Krm,
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Help and version are printed as usual.
+        Err(e) if e.use_stderr() && json_requested() => {
+            return Report::new(true).finish(Err(anyhow::Error::new(e).context(Failure::Usage)));
+        }
+        Err(e) => e.exit(),
+    };
+
+    match std::env::current_dir()
+        .map_err(anyhow::Error::from)
+        .and_then(Config::discover)
+    {
+        Ok(config) => CONFIG.get_or_init(|| config),
+        Err(e) => {
+            let json = cli.output == Some(OutputFormat::Json);
+            return Report::new(json).finish(Err(e.context(Failure::Usage)));
+        }
+    };
+
+    let output = match cli.output {
+        Some(output) => Ok(output),
+        None => configured_format("output", config().output.as_deref(), OutputFormat::Human),
+    };
+
+    let mut report = Report::new(output.as_ref().is_ok_and(|output| *output == OutputFormat::Json));
+    report.subject(cli.command.file());
+    let outcome = output.and_then(|_| run(cli.command, &mut report));
+    report.finish(outcome)
+}
+
+impl Commands {
+    /// Returns the file operated on by commands that take a single file (commands that process
+    /// several files report failures against each of them instead).
+    fn file(&self) -> Option<&str> {
+        match self {
+            Self::Decrypt { file, .. }
+            | Self::Verify { file, .. }
+            | Self::Get { file, .. }
+            | Self::Set { file, .. }
+            | Self::Generate { file, .. }
+            | Self::Ls { file, .. }
+            | Self::Rm { file, .. }
+            | Self::Mv { file, .. }
+            | Self::Cp { file, .. }
+            | Self::Init { file, .. }
+            | Self::Env { file, .. }
+            | Self::KubeSecrets { file, .. }
+            | Self::Materialize { file, .. }
+            | Self::GitTextconv { file, .. } => Some(file),
+            Self::GitMerge { ours, .. } => Some(ours),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the project configuration (the defaults until it's been discovered).
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Returns the format set in the config (under the name), or the default.
+fn configured_format<F: ValueEnum>(name: &str, format: Option<&str>, default: F) -> Result<F> {
+    match format {
+        Some(format) => F::from_str(format, true)
+            .map_err(|e| anyhow::anyhow!("invalid {} in {}: {}", name, rejson::CONFIG_FILE, e))
+            .context(Failure::Usage),
+        None => Ok(default),
+    }
+}
+
+/// Returns the supplied format, the one configured for the command under [formats], or the default.
+fn command_format<F: ValueEnum>(format: Option<F>, command: &str, default: F) -> Result<F> {
+    match format {
+        Some(format) => Ok(format),
+        None => configured_format(
+            &format!("formats.{}", command),
+            config().formats.get(command).map(String::as_str),
+            default,
+        ),
+    }
+}
+
+/// Returns whether `--output json` was supplied, for reporting arguments that can't be parsed.
+fn json_requested() -> bool {
+    let args = std::env::args().collect::<Vec<_>>();
+    args.iter().any(|arg| arg == "--output=json") || args.windows(2).any(|w| w[0] == "--output" && w[1] == "json")
+}
+
+fn run(command: Commands, report: &mut Report) -> Result<()> {
+    match command {
+        Commands::Encrypt {
+            file,
+            recursive,
+            filter,
+            fail_fast,
+        } => encrypt(file, recursive, filter, fail_fast, report),
+        Commands::Decrypt {
+            file,
+            keydir,
+            key_from_stdin,
+            out,
+            strip_key,
+        } => decrypt(file, keydir, key_from_stdin, out, strip_key, report),
+        Commands::Check {
+            paths,
+            format,
+            filter,
+            fail_fast,
+        } => check(
+            paths,
+            command_format(format, "check", CheckFormat::Human)?,
+            filter,
+            fail_fast,
+            report,
+        ),
+        Commands::Get {
+            file,
+            key,
+            keydir,
+            key_from_stdin,
+        } => get(file, key, keydir, key_from_stdin, report),
+        Commands::Set {
+            file,
+            key,
+            create,
+            json,
+        } => set(file, key, create, json, report),
+        Commands::Expiring { paths, within, format } => {
+            let format = command_format(format, "expiring", OutputFormat::Human)?;
+            expiring(paths, within, format, report)
+        }
+        Commands::Inventory { paths, format } => inventory(
+            paths,
+            command_format(format, "inventory", InventoryFormat::Markdown)?,
+            report,
+        ),
+        Commands::Doctor { paths, keydir } => doctor(paths, keydir, report),
+        Commands::Generate {
+            file,
+            key,
+            kind,
+            charset,
+            length,
+            create,
+            if_missing,
+            force,
+            show,
+        } => generate(
+            GenerateArgs {
+                file,
+                key,
+                kind,
+                charset,
+                length,
+                create,
+                if_missing,
+                force,
+                show,
+            },
+            report,
+        ),
+        Commands::Ls { file, key } => ls(file, key, report),
+        Commands::Rm { file, key } => rm(file, key, report),
+        Commands::Mv {
+            file,
+            from,
+            to,
+            create,
+            force,
+        } => mv(file, from, to, create, force, report),
+        Commands::Cp {
+            file,
+            from,
+            to,
+            to_file,
+            create,
+            force,
+            keydir,
+            key_from_stdin,
+        } => cp(
+            CpArgs {
+                file,
+                from,
+                to,
+                to_file,
+                create,
+                force,
+                keydir,
+                key_from_stdin,
+            },
+            report,
+        ),
+        Commands::Verify {
+            file,
+            keydir,
+            key_from_stdin,
+        } => verify(file, keydir, key_from_stdin, report),
+        Commands::Diff {
+            from,
+            to,
+            keydir,
+            show_values,
+        } => diff(from, to, keydir, show_values, report),
+        Commands::Keygen { keydir, write } => keygen(keydir, write, report),
+        Commands::Init {
+            file,
+            keydir,
+            public_key,
+            env,
+            kube_secrets,
+        } => init(
+            InitArgs {
+                file,
+                keydir,
+                public_key,
+                env,
+                kube_secrets,
+            },
+            report,
+        ),
+        Commands::Env {
+            file,
+            keydir,
+            key_from_stdin,
+            out,
+        } => export_env(file, keydir, key_from_stdin, out, report),
+        Commands::KubeSecrets {
+            file,
+            keydir,
+            key_from_stdin,
+            out,
+            out_dir,
+            kustomization,
+            diff,
+            labels,
+            annotations,
+            string_data,
+            namespace,
+            filter_namespace,
+        } => kube_secrets_manifest(
+            file,
+            keydir,
+            key_from_stdin,
+            KubeOutput {
+                out,
+                out_dir,
+                kustomization,
+                diff,
+            },
+            KubeOptions {
+                labels,
+                annotations,
+                string_data,
+                namespace,
+                filter_namespace,
+            },
+            report,
+        ),
+        Commands::Materialize {
+            file,
+            keydir,
+            key_from_stdin,
+            root,
+            dir,
+            uid,
+            gid,
+            clean,
+        } => materialize(
+            MaterializeArgs {
+                file,
+                keydir,
+                key_from_stdin,
+                root,
+                dir,
+                uid,
+                gid,
+                clean,
+            },
+            report,
+        ),
+        Commands::GitTextconv { file, keydir } => git_textconv(file, keydir, report),
+        Commands::GitMerge {
+            base,
+            ours,
+            theirs,
+            keydir,
+        } => git_merge(base, ours, theirs, keydir, report),
+        Commands::Git { command } => match command {
+            GitCommands::Setup { pattern } => git_setup(pattern, report),
+        },
+        Commands::Config { command } => match command {
+            ConfigCommands::Show { keydir } => config_show(keydir, report),
+        },
+        Commands::Krm => krm(report),
+    }
+}
+
+fn encrypt(
+    paths: Vec<String>,
+    recursive: bool,
+    filter: FileFilter,
+    fail_fast: bool,
+    report: &mut Report,
+) -> Result<()> {
+    let files = find_files(&paths, recursive, &filter)?;
+    let results = process_files(&files, fail_fast, encrypt_file);
+
+    let mut summary = BatchSummary::default();
+    files.iter().zip(results).for_each(|(file, result)| match result {
+        Some(Ok(encrypted)) => {
+            report.values(encrypted.values, encrypted.skipped);
+            report.fingerprint(encrypted.public_key);
+
+            match encrypted.written {
+                Some(written) => {
+                    report.wrote(file.display(), Some(written));
+                    summary.changed += 1;
+                }
+                None => {
+                    report.file(file.display(), FileStatus::Unchanged, None);
+                    summary.unchanged += 1;
+                }
+            }
+        }
+        Some(Err(e)) => {
+            report.failed(file.display(), &e);
+            summary.failed += 1;
+        }
+        None => {
+            report.file(file.display(), FileStatus::Skipped, None);
+            summary.skipped += 1;
+        }
+    });
+
+    report.println(&summary);
+    match summary.failed {
+        0 => Ok(()),
+        failed => Err(anyhow::anyhow!("Failed to encrypt {} file(s)", failed)),
+    }
+}
+
+/// The outcome of encrypting a single file.
+struct EncryptedFile {
+    /// The number of bytes written, or `None` when the file was unchanged.
+    written: Option<usize>,
+    /// The number of values that were encrypted.
+    values: usize,
+    /// The number of values that were already encrypted.
+    skipped: usize,
+    public_key: Option<Key>,
+}
+
+/// Encrypts the file, writing it when it changed.
+fn encrypt_file(path: &Path) -> Result<EncryptedFile> {
+    let contents = fs::read_to_string(path)?;
+    let mut secrets_file: SecretsFile = contents.parse()?;
+
+    let changed = secrets_file.plaintext_keys();
+    let skipped = secrets_file
+        .keys(None)?
+        .iter()
+        .filter(|(_, encrypted)| *encrypted)
+        .count();
+    secrets_file.transform(rejson::compact()?)?;
+    secrets_file.transform(rejson::encrypt(&secrets_file).context(Failure::MissingKey)?)?;
+
+    let now = SystemTime::now();
+    changed
+        .iter()
+        .try_for_each(|key| secrets_file.set_rotated_at(key, now).map(|_| ()))?;
+
+    let json = secrets_file.to_string();
+    let written = match json == contents {
+        true => None,
+        false => {
+            fs::write(path, &json)?;
+            Some(json.len())
+        }
+    };
+
+    Ok(EncryptedFile {
+        written,
+        values: changed.len(),
+        skipped,
+        public_key: secrets_file.public_key(),
+    })
+}
+
+fn decrypt(
+    file: String,
+    keydir: Option<String>,
+    key_from_stdin: bool,
+    out: Option<String>,
+    strip_key: bool,
+    report: &mut Report,
+) -> Result<()> {
+    let mut secrets_file = SecretsFile::load(&file)?;
+    report.fingerprint(secrets_file.public_key());
+
+    let private_key = load_private_key(&file, &secrets_file, keydir, key_from_stdin)?;
+    secrets_file
+        .transform(rejson::decrypt(&secrets_file, private_key)?)
+        .context(Failure::Decryption)?;
+
+    if strip_key {
+        // Useful for things like exporting tfvars without wanting to see the warning
+        // about an unknown variable. Clearly you could do this on the CLI, but we've
+        // got a tool, so you know...make it do what you want.
+        secrets_file = secrets_file.without_public_key();
+    }
+
+    if let Some(path) = out {
+        let json = secrets_file.to_string();
+        fs::write(&path, &json)?;
+        report.file(path, FileStatus::Written, Some(json.len()));
+    } else {
+        report.println(&secrets_file);
+        report.result(secrets_file.to_json());
+    }
+
+    Ok(())
+}
+
+fn get(file: String, key: String, keydir: Option<String>, key_from_stdin: bool, report: &mut Report) -> Result<()> {
+    let mut secrets_file = SecretsFile::load(&file)?;
+    report.fingerprint(secrets_file.public_key());
+
+    let private_key = load_private_key(&file, &secrets_file, keydir, key_from_stdin)?;
+    secrets_file
+        .transform_key(&key, rejson::decrypt(&secrets_file, private_key)?)
+        .context(Failure::Decryption)?;
+
+    match secrets_file.get(&key) {
+        Some(serde_json::Value::String(s)) => report.println(s),
+        Some(value) => report.println(serde_json::to_string_pretty(value)?),
+        None => return Err(anyhow::anyhow!("{} not found", key)),
+    }
+
+    report.result(secrets_file.get(&key).cloned().unwrap_or_default());
+    Ok(())
+}
+
+fn set(file: String, key: String, create: bool, json: bool, report: &mut Report) -> Result<()> {
+    let contents = fs::read_to_string(&file)?;
+    let mut secrets_file: SecretsFile = contents.parse()?;
+    report.fingerprint(secrets_file.public_key());
+
+    let input = read_value(&key)?;
+    let value = match json {
+        true => serde_json::from_str(&input)?,
+        false => serde_json::Value::String(input),
+    };
+
+    secrets_file.set(&key, value, create)?;
+    secrets_file.transform_key(&key, rejson::compact()?)?;
+    secrets_file.transform_key(&key, rejson::encrypt(&secrets_file).context(Failure::MissingKey)?)?;
+    secrets_file.set_rotated_at(&key, SystemTime::now())?;
+
+    save(&file, &secrets_file, &contents, report)
+}
+
+fn expiring(paths: Vec<String>, within: Duration, format: OutputFormat, report: &mut Report) -> Result<()> {
+    let files = find_files(&paths, true, &FileFilter::default())?;
+
+    let now = SystemTime::now();
+    let mut expiring = vec![];
+    files.iter().try_for_each(|file| -> Result<()> {
+        SecretsFile::load(file)
+            .and_then(|secrets_file| {
+                secrets_file.metadata().into_iter().try_for_each(|metadata| {
+                    if metadata.expires_within(within, now)? {
+                        expiring.push((file.display().to_string(), metadata));
+                    }
+
+                    Ok(())
+                })
+            })
+            .with_context(|| format!("reading {}", file.display()))
+    })?;
+
+    match format {
+        _ if report.is_json() => report.result(expiring_json(&expiring, now)?),
+        OutputFormat::Human if expiring.is_empty() => println!("Nothing expires within {}", format_duration(within)),
+        OutputFormat::Human => expiring.iter().try_for_each(|(file, metadata)| -> Result<()> {
+            let expires_in = metadata.expires_in(now)?.unwrap_or_default();
+            let owner = metadata
+                .owner
+                .as_ref()
+                .map(|owner| format!(", owned by {}", owner))
+                .unwrap_or_default();
+
+            println!(
+                "{}: {} expires {} ({}){}",
+                file,
+                metadata.key,
+                metadata.expires_at.as_deref().unwrap_or_default(),
+                expires_in,
+                owner
+            );
+            Ok(())
+        })?,
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&expiring_json(&expiring, now)?)?),
+    }
+
+    Ok(())
+}
+
+/// Returns the expiring values as JSON, with whether each one has already expired.
+fn expiring_json(expiring: &[(String, rejson::Metadata)], now: SystemTime) -> Result<serde_json::Value> {
+    let secrets = expiring
+        .iter()
+        .map(|(file, metadata)| -> Result<serde_json::Value> {
+            let mut json = metadata.to_json();
+            json["file"] = file.as_str().into();
+            json["expired"] = metadata.expires().map(|at| at.is_some_and(|at| at <= now))?.into();
+            Ok(json)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(json!({ "secrets": secrets }))
+}
+
+fn inventory(paths: Vec<String>, format: InventoryFormat, report: &mut Report) -> Result<()> {
+    let files = find_files(&paths, true, &FileFilter::default())?;
+
+    let mut inventory = Inventory::default();
+    files.iter().try_for_each(|file| -> Result<()> {
+        let secrets_file = SecretsFile::load(file).with_context(|| format!("reading {}", file.display()))?;
+        inventory.add(file.display().to_string(), &secrets_file);
+        Ok(())
+    })?;
+
+    match format {
+        _ if report.is_json() => report.result(inventory.to_json()),
+        InventoryFormat::Markdown => print!("{}", inventory.to_markdown()),
+        InventoryFormat::Csv => print!("{}", inventory.to_csv()),
+        InventoryFormat::Json => println!("{}", serde_json::to_string_pretty(&inventory.to_json())?),
+    }
+
+    Ok(())
+}
+
+fn doctor(paths: Vec<String>, keydir: Option<String>, report: &mut Report) -> Result<()> {
+    let files = match paths.is_empty() {
+        true => vec![],
+        false => find_files(&paths, true, &FileFilter::default())?,
+    };
+
+    // Keys are looked up the way load_private_key does: a keydir flag applies to every file,
+    // otherwise a matching rule's keydir is used before the key_env and keydir settings.
+    let config = config();
+    let key_env = config
+        .key_env
+        .as_ref()
+        .and_then(|var| std::env::var(var).ok().map(|key| (var, key)));
+    let keydir_flag = keydir.is_some();
+    let source_for = |path: &Path| match keydir_flag {
+        true => None,
+        false => match config.rules_for(path).find_map(|rule| rule.keydir.as_ref()) {
+            Some(dir) => Some(KeySource::Keydir(dir.into())),
+            None => key_env
+                .as_ref()
+                .map(|(var, key)| KeySource::Env(var.to_string(), key.trim().parse().ok())),
+        },
+    };
+
+    let warn = keydir.is_none() && config.keydir.is_none() && key_env.is_none();
+    let keydir = keydir.or_else(|| config.keydir.clone());
+    let mut diagnosis = rejson::diagnose_with(
+        Path::new(keydir.as_deref().unwrap_or(DEFAULT_KEYDIR)),
+        &files,
+        source_for,
+    );
+    if warn {
+        diagnosis.problems.insert(
+            0,
+            Diagnostic {
+                path: DEFAULT_KEYDIR.into(),
+                severity: Severity::Warning,
+                message: "EJSON_KEYDIR isn't set, so the default keydir is used".into(),
+                fix: Some(format!(
+                    "export EJSON_KEYDIR=/path/to/keys (or set keydir in {}) if your keys are kept elsewhere",
+                    rejson::CONFIG_FILE
+                )),
+            },
+        );
+    }
+
+    diagnosis
+        .keys
+        .iter()
+        .for_each(|key| report.fingerprint(Some(key.clone())));
+    match report.is_json() {
+        true => {
+            diagnosis.problems.iter().for_each(|p| match p.severity {
+                Severity::Error => report.error(Some(&p.path), None, Some(Failure::Validation), &p.message),
+                Severity::Warning => report.warning(Some(&p.path), None, &p.message),
+            });
+            report.result(diagnosis.to_json());
+        }
+        false => print!("{}", diagnosis),
+    }
+
+    match diagnosis.is_ok() {
+        true => Ok(()),
+        false => {
+            let errors = diagnosis
+                .problems
+                .iter()
+                .filter(|p| p.severity == Severity::Error)
+                .count();
+            Err(anyhow::anyhow!("Found {} problem(s)", errors).context(Failure::Validation))
+        }
+    }
+}
+
+struct GenerateArgs {
+    file: String,
+    key: String,
+    kind: GenerateKind,
+    charset: GenerateCharset,
+    length: Option<usize>,
+    create: bool,
+    if_missing: bool,
+    force: bool,
+    show: bool,
+}
+
+fn generate(args: GenerateArgs, report: &mut Report) -> Result<()> {
+    let contents = fs::read_to_string(&args.file)?;
+    let mut secrets_file: SecretsFile = contents.parse()?;
+    report.fingerprint(secrets_file.public_key());
+
+    if secrets_file.get(&args.key).is_some() {
+        if args.if_missing {
+            let message = format!("{} already exists, skipping", args.key);
+            report.warning(Some(&args.file), Some(&args.key), message);
+            return Ok(());
+        }
+
+        if !args.force {
+            return Err(
+                anyhow::anyhow!("{} already exists (use --force to overwrite it)", args.key).context(Failure::Usage),
+            );
+        }
+    }
+
+    let generator = match args.kind {
+        GenerateKind::Random => {
+            let charset = match args.charset {
+                GenerateCharset::Alnum => Charset::Alnum,
+                GenerateCharset::Hex => Charset::Hex,
+                GenerateCharset::Base64 => Charset::Base64,
+                GenerateCharset::Words => Charset::Words,
+            };
+            let default_length = match charset {
+                Charset::Words => rejson::DEFAULT_WORDS,
+                _ => rejson::DEFAULT_LENGTH,
+            };
+
+            Generator::Random {
+                charset,
+                length: args.length.unwrap_or(default_length),
+            }
+        }
+        GenerateKind::Uuid => Generator::Uuid,
+        GenerateKind::Ed25519 => Generator::Ed25519,
+    };
+
+    let value = generator.generate().context(Failure::Usage)?;
+    secrets_file.set(&args.key, value.clone(), args.create)?;
+    secrets_file.transform_key(&args.key, rejson::encrypt(&secrets_file).context(Failure::MissingKey)?)?;
+    secrets_file.set_rotated_at(&args.key, SystemTime::now())?;
+
+    save(&args.file, &secrets_file, &contents, report)?;
+
+    if args.show {
+        match &value {
+            serde_json::Value::String(s) => report.println(s),
+            value => report.println(serde_json::to_string_pretty(value)?),
+        }
+
+        report.result(value);
+    }
+
+    Ok(())
+}
+
+fn ls(file: String, key: Option<String>, report: &mut Report) -> Result<()> {
+    let secrets_file = SecretsFile::load(file)?;
+    let keys = secrets_file.keys(key.as_deref())?;
+
+    keys.iter().for_each(|(key, encrypted)| {
+        let status = if *encrypted { "encrypted" } else { "plain" };
+        report.println(format!("{:<9}  {}", status, key));
+    });
+
+    report.result(
+        keys.iter()
+            .map(|(key, encrypted)| json!({ "key": key, "encrypted": encrypted }))
+            .collect(),
+    );
+    Ok(())
+}
+
+fn rm(file: String, key: String, report: &mut Report) -> Result<()> {
+    let contents = fs::read_to_string(&file)?;
+    let mut secrets_file: SecretsFile = contents.parse()?;
+
+    secrets_file.remove(&key)?;
+    secrets_file.remove_metadata(&key);
+    save(&file, &secrets_file, &contents, report)
+}
+
+fn mv(file: String, from: String, to: String, create: bool, force: bool, report: &mut Report) -> Result<()> {
+    let contents = fs::read_to_string(&file)?;
+    let mut secrets_file: SecretsFile = contents.parse()?;
+    report.fingerprint(secrets_file.public_key());
+
+    if !force && secrets_file.get(&to).is_some() {
+        return Err(anyhow::anyhow!("{} already exists (use --force to overwrite it)", to).context(Failure::Usage));
+    }
+
+    let value = secrets_file.remove(&from)?;
+    secrets_file.set(&to, value, create)?;
+    secrets_file.transform_key(&to, rejson::encrypt(&secrets_file).context(Failure::MissingKey)?)?;
+
+    if let Some(metadata) = secrets_file.remove_metadata(&from) {
+        secrets_file.insert_metadata(&to, metadata)?;
+    }
+
+    save(&file, &secrets_file, &contents, report)
+}
+
+struct CpArgs {
+    file: String,
+    from: String,
+    to: String,
+    to_file: Option<String>,
+    create: bool,
+    force: bool,
+    keydir: Option<String>,
+    key_from_stdin: bool,
+}
+
+fn cp(args: CpArgs, report: &mut Report) -> Result<()> {
+    let mut source = SecretsFile::load(&args.file)?;
+    let dest_path = args.to_file.unwrap_or_else(|| args.file.clone());
+    let contents = fs::read_to_string(&dest_path)?;
+    let mut dest: SecretsFile = contents.parse()?;
+    report.fingerprint(source.public_key());
+    report.fingerprint(dest.public_key());
+
+    if !args.force && dest.get(&args.to).is_some() {
+        return Err(
+            anyhow::anyhow!("{} already exists (use --force to overwrite it)", args.to).context(Failure::Usage),
+        );
+    }
+
+    // Encrypted values can only be copied as is when the destination uses the same key pair.
+    let encrypted = source.keys(Some(&args.from))?.iter().any(|(_, encrypted)| *encrypted);
+    if encrypted && source.public_key() != dest.public_key() {
+        let private_key = load_private_key(&args.file, &source, args.keydir, args.key_from_stdin)?;
+        source
+            .transform_key(&args.from, rejson::decrypt(&source, private_key)?)
+            .context(Failure::Decryption)?;
+    }
+
+    let value = source.get(&args.from).cloned().unwrap_or_default();
+    dest.set(&args.to, value, args.create)?;
+    dest.transform_key(&args.to, rejson::encrypt(&dest).context(Failure::MissingKey)?)?;
+    dest.set_rotated_at(&args.to, SystemTime::now())?;
+
+    save(&dest_path, &dest, &contents, report)
+}
+
+/// Writes the secrets file, keeping the formatting of its existing contents.
+fn save(file: &str, secrets_file: &SecretsFile, existing: &str, report: &mut Report) -> Result<()> {
+    let json = secrets_file.to_string_like(existing)?;
+    fs::write(file, &json)?;
+    report.wrote(file, Some(json.len()));
+
+    Ok(())
+}
+
+/// Reads a value from stdin, prompting for it (without echoing) when stdin is a terminal.
+fn read_value(key: &str) -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password(format!("{}: ", key))?);
+    }
+
+    let mut value = String::new();
+    std::io::stdin().read_to_string(&mut value)?;
+
+    // Drop the new line added by things like echo.
+    let len = value.trim_end_matches(['\n', '\r']).len();
+    value.truncate(len);
+
+    Ok(value)
+}
+
+fn check(
+    paths: Vec<String>,
+    format: CheckFormat,
+    filter: FileFilter,
+    fail_fast: bool,
+    report: &mut Report,
+) -> Result<()> {
+    let files = find_files(&paths, true, &filter)?;
+    let results = process_files(&files, fail_fast, |file| Ok(fs::read_to_string(file)?));
+
+    let mut check_report = CheckReport::default();
+    let (mut checked, mut failed) = (0, 0);
+    for (file, result) in files.iter().zip(results) {
+        match result {
+            Some(Ok(contents)) => {
+                check_report.check(file.display().to_string(), &contents);
+                checked += 1;
+            }
+            Some(Err(e)) => {
+                report.failed(file.display(), &e);
+                failed += 1;
+            }
+            None => {}
+        }
+
+        if fail_fast && (failed > 0 || !check_report.is_clean()) {
+            break;
+        }
+    }
+
+    check_report.findings().for_each(|(file, finding)| {
+        report.error(
+            Some(file),
+            finding.path.as_deref(),
+            Some(Failure::Validation),
+            &finding.message,
+        );
+    });
+
+    match format {
+        _ if report.is_json() => report.result(check_report.to_json()),
+        CheckFormat::Human if check_report.is_clean() => println!("Checked {} file(s)", checked),
+        CheckFormat::Human => print!("{}", check_report),
+        CheckFormat::Json => println!("{}", serde_json::to_string_pretty(&check_report.to_json())?),
+        CheckFormat::Sarif => println!("{}", serde_json::to_string_pretty(&check_report.to_sarif())?),
+    }
+
+    match (check_report.is_clean(), failed) {
+        (false, _) => {
+            Err(anyhow::anyhow!("Found {} problem(s)", check_report.findings().count()).context(Failure::Validation))
+        }
+        (true, 0) => Ok(()),
+        (true, failed) => Err(anyhow::anyhow!("Failed to read {} file(s)", failed)),
+    }
+}
+
+fn verify(file: String, keydir: Option<String>, key_from_stdin: bool, report: &mut Report) -> Result<()> {
+    let secrets_file = SecretsFile::load(&file)?;
+    report.fingerprint(secrets_file.public_key());
+    let private_key = load_private_key(&file, &secrets_file, keydir, key_from_stdin)?;
+
+    let verification = rejson::verify(&secrets_file, private_key)?;
+    report.print(&verification);
+    report.println(format!("Verified {} value(s) in {}", verification.verified, file));
+
+    if report.is_json() {
+        verification.problems.iter().for_each(|problem| match problem.severity {
+            Severity::Error => report.error(
+                Some(&file),
+                Some(&problem.path),
+                Some(Failure::Decryption),
+                &problem.message,
+            ),
+            Severity::Warning => report.warning(Some(&file), Some(&problem.path), &problem.message),
+        });
+    }
+
+    report.result(verification.to_json());
+    match verification.is_ok() {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("Some values in {} could not be decrypted", file).context(Failure::Decryption)),
+    }
+}
+
+/// Returns the files to process: explicit files as is, the files in directories (when
+/// `recursive` is set) and the files matching glob patterns. Directories are walked skipping hidden
+/// and git ignored files, and the filter's include and exclude patterns are applied to the files
+/// that are found.
+fn find_files(paths: &[String], recursive: bool, filter: &FileFilter) -> Result<Vec<PathBuf>> {
+    // Patterns supplied on the command line replace the configured ones.
+    let config = config();
+    let include = match (filter.include.is_empty(), config.include.is_empty()) {
+        (false, _) => Patterns::new(&filter.include),
+        (true, false) => Patterns::new(&config.include),
+        (true, true) => Patterns::new(&[DEFAULT_INCLUDE.to_string()]),
+    }
+    .context(Failure::Usage)?;
+    let exclude = match filter.exclude.is_empty() {
+        true => Patterns::new(&config.exclude),
+        false => Patterns::new(&filter.exclude),
+    }
+    .context(Failure::Usage)?;
+
+    let mut files = vec![];
+    paths.iter().try_for_each(|path| -> Result<()> {
+        let file = Path::new(path);
+
+        if !file.exists() && path.contains(GLOB_CHARS) {
+            let glob = GlobBuilder::new(path.trim_start_matches("./"))
+                .literal_separator(true)
+                .build()
+                .context(Failure::Usage)?
+                .compile_matcher();
+            let base = file
+                .components()
+                .take_while(|c| !c.as_os_str().to_string_lossy().contains(GLOB_CHARS))
+                .collect::<PathBuf>();
+
+            return walk(&base, &mut files, |path, relative| {
+                glob.is_match(path.strip_prefix(".").unwrap_or(path)) && !exclude.is_match(relative)
+            });
+        }
+
+        if file.is_dir() {
+            if !recursive {
+                return Err(
+                    anyhow::anyhow!("{} is a directory (use --recursive to include its files)", path)
+                        .context(Failure::Usage),
+                );
+            }
+
+            return walk(file, &mut files, |_, relative| {
+                include.is_match(relative) && !exclude.is_match(relative)
+            });
+        }
+
+        files.push(file.to_path_buf());
+        Ok(())
+    })?;
+
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.clone()));
+    Ok(files)
+}
+
+/// Walks the directory (skipping hidden and git ignored files), adding the files accepted by the
+/// supplied function, which receives their path and their path relative to the directory.
+fn walk<F: Fn(&Path, &Path) -> bool>(dir: &Path, files: &mut Vec<PathBuf>, accept: F) -> Result<()> {
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+
+    WalkBuilder::new(dir)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+        .try_for_each(|entry| -> Result<()> {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+
+            if entry.file_type().is_some_and(|t| t.is_file()) && accept(path, relative) {
+                files.push(path.to_path_buf());
+            }
+
+            Ok(())
+        })
+}
+
+/// Glob patterns that match a file's name (patterns without a /) or its relative path.
+struct Patterns {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl Patterns {
+    fn new(patterns: &[String]) -> Result<Self> {
+        let (mut names, mut paths) = (GlobSetBuilder::new(), GlobSetBuilder::new());
+        patterns.iter().try_for_each(|pattern| -> Result<()> {
+            let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()?;
+
+            match pattern.contains('/') {
+                true => paths.add(glob),
+                false => names.add(glob),
+            };
+
+            Ok(())
+        })?;
+
+        Ok(Self {
+            names: names.build()?,
+            paths: paths.build()?,
+        })
+    }
+
+    fn is_match(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| self.names.is_match(name)) || self.paths.is_match(path)
+    }
+}
+
+/// Runs the function on each file using a thread per CPU (up to the number of files), returning
+/// the results in the order of the files. When `fail_fast` is set, files that haven't been started
+/// when one fails are skipped (`None`).
+fn process_files<T: Send, F: Fn(&Path) -> Result<T> + Sync>(
+    files: &[PathBuf],
+    fail_fast: bool,
+    f: F,
+) -> Vec<Option<Result<T>>> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results = files.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len());
+
+    std::thread::scope(|scope| {
+        (0..threads).for_each(|_| {
+            scope.spawn(|| {
+                while !(fail_fast && failed.load(Ordering::Relaxed)) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(i) else { break };
+
+                    let result = f(file);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+
+                    *results[i].lock().unwrap() = Some(result);
+                }
+            });
+        });
+    });
+
+    results.into_iter().map(|r| r.into_inner().unwrap()).collect()
+}
+
+/// Counts the outcomes of processing a batch of files.
+#[derive(Default)]
+struct BatchSummary {
+    changed: usize,
+    unchanged: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+impl std::fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.changed + self.unchanged + self.failed + self.skipped;
+        write!(
+            f,
+            "{} file(s): {} changed, {} unchanged, {} failed",
+            total, self.changed, self.unchanged, self.failed
+        )?;
+
+        match self.skipped {
+            0 => Ok(()),
+            skipped => write!(f, ", {} skipped", skipped),
+        }
+    }
+}
+
+fn diff(from: String, to: String, keydir: Option<String>, show_values: bool, report: &mut Report) -> Result<()> {
+    let mut from_file = load_revision(&from)?;
+    let mut to_file = load_revision(&to)?;
+    report.fingerprint(from_file.public_key());
+    report.fingerprint(to_file.public_key());
+
+    // Without both keys, values can only be compared by their ciphertext, which is only meaningful
+    // when both files use the same key.
+    let decrypted = [(&from, &mut from_file), (&to, &mut to_file)]
+        .into_iter()
+        .map(|(spec, secrets_file)| -> Result<bool> {
+            let Some(key) = secrets_file
+                .public_key()
+                .and_then(|_| load_private_key(revision_path(spec), secrets_file, keydir.clone(), false).ok())
+            else {
+                return Ok(false);
+            };
+
+            secrets_file
+                .transform(rejson::decrypt_binary(secrets_file, key)?)
+                .context(Failure::Decryption)?;
+            Ok(true)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if decrypted.contains(&false) && from_file.public_key() != to_file.public_key() {
+        return Err(anyhow::anyhow!(
+            "The files use different keys, so the private keys for both are required to compare values"
+        )
+        .context(Failure::MissingKey));
+    }
+
+    let diff = SecretsDiff::new(&from_file.into(), &to_file.into());
+    let diff = match show_values {
+        true => diff,
+        false => diff.masked(),
+    };
+
+    report.print(&diff);
+    report.result(diff.to_json());
+
+    match diff.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("{} and {} differ", from, to)),
+    }
+}
+
+/// Returns the path of a FILE or FILE@REV.
+fn revision_path(spec: &str) -> &str {
+    match spec.rsplit_once('@') {
+        Some((path, _)) if !Path::new(spec).exists() => path,
+        _ => spec,
+    }
+}
+
+/// Loads a secrets file from disk or, when given as FILE@REV, from git.
+fn load_revision(spec: &str) -> Result<SecretsFile> {
+    match spec.rsplit_once('@') {
+        Some((path, rev)) if !Path::new(spec).exists() => {
+            // Paths starting with ./ are relative to the current directory rather than the repo root.
+            let path = match Path::new(path).is_absolute() {
+                true => path.to_string(),
+                false => format!("./{}", path.trim_start_matches("./")),
+            };
+
+            git(&["show", &format!("{}:{}", rev, path)])?.parse()
+        }
+        _ => SecretsFile::load(spec),
+    }
+}
+
+fn keygen(keydir: Option<String>, write: bool, report: &mut Report) -> Result<()> {
+    let keydir = keydir.or_else(|| config().keydir.clone());
+    if write && keydir.is_none() {
+        return Err(anyhow::anyhow!(
+            "Either EJSON_KEYDIR must be set, --keydir must be supplied or keydir must be set in {}",
+            rejson::CONFIG_FILE
+        )
+        .context(Failure::Usage));
+    }
+
+    let pair = KeyPair::generate().unwrap();
+    report.println("Public Key:");
+    report.println(pair.public_key());
+    report.fingerprint(pair.public_key().parse().ok());
+
+    if !write {
+        report.println("Private Key:");
+        report.println(pair.private_key());
+        report.result(json!({ "public_key": pair.public_key(), "private_key": pair.private_key() }));
+        return Ok(());
+    }
+
+    let path = write_private_key(Path::new(&keydir.unwrap()), &pair)?;
+    report.file(path.display(), FileStatus::Written, None);
+    report.result(json!({ "public_key": pair.public_key() }));
+    Ok(())
+}
+
+struct InitArgs {
+    file: String,
+    keydir: Option<String>,
+    public_key: Option<String>,
+    env: bool,
+    kube_secrets: bool,
+}
+
+fn init(args: InitArgs, report: &mut Report) -> Result<()> {
+    if Path::new(&args.file).exists() {
+        return Err(anyhow::anyhow!("{} already exists", args.file).context(Failure::Usage));
+    }
+
+    let config = config();
+    let public_key = match args.public_key {
+        Some(key) => Some(key.parse::<Key>().context(Failure::Usage)?),
+        None => config.public_key_for(&args.file).cloned(),
+    };
+
+    // Create the file before writing a key, so a key is never left behind for a file that couldn't be created.
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&args.file)
+        .with_context(|| format!("creating {}", args.file))?;
+
+    let (public_key, key_file) = match public_key {
+        Some(key) => (key, None),
+        None => {
+            let keydir = args
+                .keydir
+                .or_else(|| config.keydir_for(&args.file).map(String::from))
+                .unwrap_or_else(|| DEFAULT_KEYDIR.into());
+            let pair = KeyPair::generate()?;
+            let key_file = write_private_key(Path::new(&keydir), &pair).inspect_err(|_| {
+                let _ = fs::remove_file(&args.file);
+            })?;
+
+            (pair.public_key().parse()?, Some(key_file))
+        }
+    };
+
+    let mut value = json!({ "_public_key": public_key.to_string() });
+    if args.env {
+        value[&config.env_key] = json!({});
+    }
+    if args.kube_secrets {
+        value[&config.kube_secrets_key] = json!({});
+    }
+
+    let json = format!("{}\n", serde_json::to_string_pretty(&value)?);
+    file.write_all(json.as_bytes())
+        .with_context(|| format!("writing {}", args.file))?;
+
+    if let Some(key_file) = &key_file {
+        report.file(key_file.display(), FileStatus::Written, None);
+    }
+    report.wrote(&args.file, Some(json.len()));
+    report.println(format!("Public Key: {}", public_key));
+    report.fingerprint(Some(public_key.clone()));
+    report.result(json!({
+        "file": args.file,
+        "public_key": public_key.to_string(),
+        "key_file": key_file.map(|path| path.display().to_string()),
+    }));
+
+    Ok(())
+}
+
+/// Writes the private key to the keydir (creating it if needed) with mode 0600, returning its path. Existing keys are
+/// never overwritten.
+fn write_private_key(keydir: &Path, pair: &KeyPair) -> Result<PathBuf> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(keydir)
+        .with_context(|| format!("creating {}", keydir.display()))?;
+
+    let path = keydir.join(pair.public_key());
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(pair.private_key().as_bytes()))
+        .with_context(|| format!("writing {}", path.display()))?;
+
+    Ok(path)
+}
+
+fn export_env(
+    file: String,
+    keydir: Option<String>,
+    key_from_stdin: bool,
+    out: Option<String>,
+    report: &mut Report,
+) -> Result<()> {
+    let mut secrets_file = SecretsFile::load(&file)?;
+    report.fingerprint(secrets_file.public_key());
+
+    let private_key = load_private_key(&file, &secrets_file, keydir, key_from_stdin)?;
+    secrets_file
+        .transform(rejson::decrypt(&secrets_file, private_key)?)
+        .context(Failure::Decryption)?;
+
+    let env_key = &config().env_key;
+    match secrets_file.children(env_key) {
+        Some(map) => {
+            let map = &map;
+            report.result(map.iter().map(|(k, v)| (k.to_string(), json!(v))).collect());
+
+            match out {
+                None => {
+                    map.iter().for_each(|(k, v)| {
+                        report.println(format!("export {}={}", k, shell_escape::escape(v.to_string().into())))
+                    });
+                    Ok(())
+                }
+                Some(out) => {
+                    let mut file = fs::File::create(&out)?;
+                    map.iter()
+                        .try_for_each(|(k, v)| {
+                            writeln!(file, "export {}={}", k, shell_escape::escape(v.to_string().into()))
+                        })
+                        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+                    report.file(out, FileStatus::Written, None);
+                    Ok(())
+                }
+            }
+        }
+        None => {
+            report.warning(
+                Some(&file),
+                Some(env_key),
+                format!("No {} key found. Nothing to export.", env_key),
+            );
+            Ok(())
+        }
+    }
+}
+
+fn kube_secrets_manifest(
+    file: String,
+    keydir: Option<String>,
+    key_from_stdin: bool,
+    output: KubeOutput,
+    opts: KubeOptions,
+    report: &mut Report,
+) -> Result<()> {
+    let mut secrets_file = SecretsFile::load(&file)?;
+    report.fingerprint(secrets_file.public_key());
+    let private_key = load_private_key(&file, &secrets_file, keydir, key_from_stdin)?;
+    secrets_file
+        .transform(rejson::decrypt_binary(&secrets_file, private_key)?)
+        .context(Failure::Decryption)?;
+    let secrets: SecretsMap = secrets_file.into();
+
+    // Fail before writing anything if any of the secrets would be rejected by Kubernetes.
+    let manifest = kube_manifest(&secrets, &opts);
+    manifest.validate().context(Failure::Validation)?;
+
+    if let Some(existing) = output.diff {
+        let diff = manifest.diff(&existing)?;
+        report.print(&diff);
+        report.result(diff.to_json());
+
+        return match diff.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("Kubernetes secrets differ from {}", existing)),
+        };
+    }
+
+    if let Some(dir) = output.out_dir {
+        let summary = manifest.write_dir(&dir, file, output.kustomization)?;
+        report_dir(Path::new(&dir), &summary, report);
+        return Ok(());
+    }
+
+    match output.out {
+        None => {
+            report.println(&manifest);
+            report.result(manifest.resources().into());
+        }
+        Some(out) => {
+            let mut file = fs::File::create(&out)?;
+            writeln!(file, "{}", manifest).map_err(anyhow::Error::msg)?;
+            report.file(out, FileStatus::Written, None);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reports the files written, removed and left as is in the directory.
+fn report_dir(dir: &Path, summary: &rejson::DirSummary, report: &mut Report) {
+    summary
+        .written
+        .iter()
+        .for_each(|path| report.wrote(dir.join(path).display(), None));
+    summary
+        .unchanged
+        .iter()
+        .for_each(|path| report.file(dir.join(path).display(), FileStatus::Unchanged, None));
+    summary
+        .removed
+        .iter()
+        .for_each(|path| report.removed(dir.join(path).display()));
+}
+
+fn krm(report: &mut Report) -> Result<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let mut list: ResourceList = input.parse()?;
+
+    match list.config() {
+        Ok(config) => config.files.iter().for_each(|file| match krm_resources(file, &config) {
+            Ok(items) => list.append_items(items),
+            Err(e) => list.add_error(format!("{:#}", e), Some(file)),
+        }),
+        Err(e) => list.add_error(e.to_string(), None),
+    }
+
+    report.print(&list);
+    report.result(list.to_json());
+
+    if list.has_errors() {
+        return Err(anyhow::anyhow!("Failed to generate secrets (see results for details)"));
+    }
+
+    Ok(())
+}
+
+/// Generates the Secret resources for the given file using the KRM function config.
+fn krm_resources(file: &str, config: &KrmConfig) -> Result<Vec<serde_json::Value>> {
+    let mut secrets_file = SecretsFile::load(file)?;
+    let private_key = match &config.key_env {
+        Some(var) => std::env::var(var)
+            .map_err(|_| anyhow::anyhow!("{} is not set", var))?
+            .trim()
+            .parse()?,
+        None => load_private_key(
+            file,
+            &secrets_file,
+            config.keydir.clone().or_else(|| std::env::var("EJSON_KEYDIR").ok()),
+            false,
+        )?,
+    };
+
+    secrets_file.transform(rejson::decrypt_binary(&secrets_file, private_key)?)?;
+    let secrets: SecretsMap = secrets_file.into();
+
+    let opts = KubeOptions {
+        labels: config.labels.clone().into_iter().collect(),
+        annotations: config.annotations.clone().into_iter().collect(),
+        string_data: config.string_data,
+        namespace: config.namespace.clone(),
+        filter_namespace: vec![],
+    };
+
+    let manifest = kube_manifest(&secrets, &opts);
+    manifest.validate()?;
+    Ok(manifest.resources())
+}
+
+struct MaterializeArgs {
+    file: String,
+    keydir: Option<String>,
+    key_from_stdin: bool,
+    root: Option<String>,
+    dir: String,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    clean: bool,
+}
+
+fn materialize(args: MaterializeArgs, report: &mut Report) -> Result<()> {
+    let mut secrets_file = SecretsFile::load(&args.file)?;
+    report.fingerprint(secrets_file.public_key());
+
+    let private_key = load_private_key(&args.file, &secrets_file, args.keydir, args.key_from_stdin)?;
+    secrets_file
+        .transform(rejson::decrypt_binary(&secrets_file, private_key)?)
+        .context(Failure::Decryption)?;
+
+    let summary = SecretsDir::new(&secrets_file, args.root.as_deref())?
+        .with_owner(args.uid, args.gid)
+        .with_clean(args.clean)
+        .write(&args.dir)?;
+
+    report_dir(Path::new(&args.dir), &summary, report);
+    Ok(())
+}
+
+fn git_textconv(file: String, keydir: Option<String>, report: &mut Report) -> Result<()> {
+    let contents = fs::read_to_string(&file)?;
+    let Ok(mut secrets_file) = contents.parse::<SecretsFile>() else {
+        // Not valid JSON (e.g. it has conflict markers), so there's nothing to decrypt.
+        report.print(&contents);
+        report.result(contents.into());
+        return Ok(());
+    };
+    report.fingerprint(secrets_file.public_key());
+
+    let private_key = secrets_file
+        .public_key()
+        .and_then(|_| load_private_key(&file, &secrets_file, keydir, false).ok());
+
+    let redact = rejson::redact()?;
+    match private_key {
+        // A value that can't be decrypted (e.g. one encrypted for another key) is redacted rather
+        // than failing the whole diff.
+        Some(key) => {
+            let decrypt = rejson::decrypt_binary(&secrets_file, key)?;
+            secrets_file.transform(|s: String| decrypt(s.clone()).or_else(|_| redact(s)))?
+        }
+        None => secrets_file.transform(redact)?,
+    }
+
+    report.println(&secrets_file);
+    report.result(secrets_file.to_json());
+    Ok(())
+}
+
+fn git_merge(base: String, ours: String, theirs: String, keydir: Option<String>, report: &mut Report) -> Result<()> {
+    let contents = fs::read_to_string(&ours)?;
+    let ours_file: SecretsFile = contents.parse()?;
+    let theirs_file = SecretsFile::load(&theirs)?;
+
+    // There's no common ancestor when both sides added the file.
+    let base_contents = fs::read_to_string(&base)?;
+    let base_file: SecretsFile = match base_contents.trim().is_empty() {
+        true => "{}".parse()?,
+        false => base_contents.parse()?,
+    };
+
+    let decrypt = ours_file
+        .public_key()
+        .and_then(|_| load_private_key(&ours, &ours_file, keydir, false).ok())
+        .map(|key| rejson::decrypt_binary(&ours_file, key))
+        .transpose()?;
+
+    let merged = rejson::merge(&base_file, &ours_file, &theirs_file, decrypt)?;
+    let json = merged.file.to_string_like(&contents)?;
+    fs::write(&ours, &json)?;
+    report.fingerprint(ours_file.public_key());
+    report.file(&ours, FileStatus::Written, Some(json.len()));
+
+    if !merged.conflicts.is_empty() {
+        merged.conflicts.iter().for_each(|path| {
+            let message = "was changed in both versions";
+            if !report.is_json() {
+                eprintln!("CONFLICT: {} {}", path, message);
+            }
+
+            report.error(Some(&ours), Some(path), None, message);
+        });
+
+        return Err(anyhow::anyhow!(
+            "Failed to merge {} value(s); kept our version of each",
+            merged.conflicts.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn git_setup(pattern: String, report: &mut Report) -> Result<()> {
+    let root = git(&["rev-parse", "--show-toplevel"])?;
+    let attributes_path = Path::new(root.trim()).join(".gitattributes");
+    let attributes = fs::read_to_string(&attributes_path).unwrap_or_default();
+    let entry = format!("{} diff={} merge={}", pattern, GIT_DRIVER, GIT_DRIVER);
+
+    if !attributes.lines().any(|line| line.trim() == entry) {
+        let separator = match attributes.is_empty() || attributes.ends_with('\n') {
+            true => "",
+            false => "\n",
+        };
+
+        fs::write(&attributes_path, format!("{}{}{}\n", attributes, separator, entry))?;
+        report.println(format!("Added \"{}\" to {}", entry, attributes_path.display()));
+        report.file(attributes_path.display(), FileStatus::Written, None);
+    }
+
+    git(&[
+        "config",
+        &format!("diff.{}.textconv", GIT_DRIVER),
+        "rejson git-textconv",
+    ])?;
+    report.println(format!("Configured the {} diff driver", GIT_DRIVER));
+
+    git(&["config", &format!("merge.{}.name", GIT_DRIVER), "EJSON merge"])?;
+    git(&[
+        "config",
+        &format!("merge.{}.driver", GIT_DRIVER),
+        "rejson git-merge %O %A %B",
+    ])?;
+    report.println(format!("Configured the {} merge driver", GIT_DRIVER));
+    report.result(json!({ "pattern": pattern, "driver": GIT_DRIVER }));
+
+    Ok(())
+}
+
+fn config_show(keydir: Option<String>, report: &mut Report) -> Result<()> {
+    let config = config();
+    let mut settings = config.to_json();
+    let source = settings.as_object_mut().and_then(|s| s.shift_remove("path"));
+
+    settings["keydir"] = keydir
+        .or_else(|| config.keydir.clone())
+        .unwrap_or_else(|| DEFAULT_KEYDIR.into())
+        .into();
+    if config.include.is_empty() {
+        settings["include"] = json!([DEFAULT_INCLUDE]);
+    }
+    settings["output"] = format_name(match report.is_json() {
+        true => OutputFormat::Json,
+        false => OutputFormat::Human,
+    })
+    .into();
+    settings["formats"] = json!({
+        "check": format_name(command_format(None, "check", CheckFormat::Human)?),
+        "expiring": format_name(command_format(None, "expiring", OutputFormat::Human)?),
+        "inventory": format_name(command_format(None, "inventory", InventoryFormat::Markdown)?),
+    });
+
+    match source.as_ref().and_then(|s| s.as_str()) {
+        Some(path) => report.println(format!("# {}", path)),
+        None => report.println(format!("# No {} found, showing the defaults", rejson::CONFIG_FILE)),
+    }
+
+    report.print(toml::to_string(&without_nulls(settings.clone()))?);
+    settings["path"] = source.unwrap_or_default();
+    report.result(settings);
+    Ok(())
+}
+
+/// Returns the name of a format, as supplied on the command line.
+fn format_name<F: ValueEnum>(format: F) -> String {
+    format
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Removes the null values from objects (recursively), since TOML has no null.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k, without_nulls(v)))
+            .collect(),
+        serde_json::Value::Array(values) => values.into_iter().map(without_nulls).collect(),
+        value => value,
+    }
+}
+
+/// Runs git with the supplied arguments, returning its stdout.
+fn git(args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Builds the manifest for the secrets under the "kubernetes" key.
+fn kube_manifest<'a>(secrets: &'a SecretsMap, opts: &'a KubeOptions) -> SecretsManifest<'a> {
+    let kube_secrets_key = config().kube_secrets_key.as_str();
+    let prefix = format!("{}.", kube_secrets_key);
+    let manifest = SecretsManifest::new(
+        secrets
+            .iter()
+            .filter_map(|(k, v)| Some((k.strip_prefix(&prefix)?, v.as_str())))
+            .collect(),
+    )
+    .with_labels(opts.labels.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    .with_annotations(opts.annotations.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    .with_namespace_filter(opts.filter_namespace.iter().map(String::as_str))
+    .with_string_data(opts.string_data)
+    .with_root(kube_secrets_key);
+
+    match &opts.namespace {
+        Some(ns) => manifest.with_namespace(ns),
+        None => manifest,
+    }
+}
+
+/// Include and exclude patterns for the files found in directories.
+#[derive(Args, Default)]
+struct FileFilter {
+    /// Only include files matching this glob (default: *.ejson). Patterns without a / match the file name, others
+    /// match the path relative to the directory. Can be supplied multiple times.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Exclude files matching this glob, in the same form as --include. Can be supplied multiple times.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InventoryFormat {
+    Markdown,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GenerateKind {
+    Random,
+    Uuid,
+    Ed25519,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GenerateCharset {
+    Alnum,
+    Hex,
+    Base64,
+    Words,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CheckFormat {
+    Human,
+    Json,
+    Sarif,
+}
+
+#[derive(Subcommand)]
+enum GitCommands {
+    /// Configure the current repository to diff and merge EJSON files with rejson.
+    ///
+    /// Adds the diff and merge attributes for the pattern to .gitattributes and configures the `rejson git-textconv`
+    /// diff driver and the `rejson git-merge` merge driver in the repository's git config.
+    Setup {
+        /// The pattern of the files to use the drivers for.
+        #[arg(long, default_value = "*.ejson")]
+        pattern: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective settings, as TOML.
+    ///
+    /// Settings that aren't in the config file are shown with their defaults.
+    Show {
+        #[arg(env = "EJSON_KEYDIR", long)]
+        keydir: Option<String>,
+    },
+}
+
+/// Where the kube-secrets manifest is written.
+struct KubeOutput {
+    out: Option<String>,
+    out_dir: Option<String>,
+    kustomization: bool,
+    diff: Option<String>,
+}
+
+/// Options controlling how the kube-secrets manifest is generated.
+struct KubeOptions {
+    labels: Vec<(String, String)>,
+    annotations: Vec<(String, String)>,
+    string_data: bool,
+    namespace: Option<String>,
+    filter_namespace: Vec<String>,
+}
+
+/// Load the private key of the file at the path from stdin, the keydir, or the environment variable set by key_env in
+/// the config. The keydir is the supplied one, the one configured for the path, or the default.
+fn load_private_key<P: AsRef<Path>>(
+    path: P,
+    secrets_file: &SecretsFile,
+    keydir: Option<String>,
+    key_from_stdin: bool,
+) -> Result<Key> {
+    if key_from_stdin {
+        let mut buffer = String::new();
+        std::io::stdin().read_line(&mut buffer)?;
+        return buffer.trim().parse().context(Failure::MissingKey);
+    }
+
+    // A keydir for the file (from the flag or a matching rule) is more specific than the key_env
+    // and keydir settings, which apply to every file.
+    let config = config();
+    let keydir = keydir.or_else(|| config.rules_for(&path).find_map(|rule| rule.keydir.clone()));
+    if keydir.is_none()
+        && let Some(key) = config.key_env.as_ref().and_then(|var| std::env::var(var).ok())
+    {
+        return key.trim().parse().context(Failure::MissingKey);
+    }
+
+    let keydir = keydir
+        .or_else(|| config.keydir.clone())
+        .unwrap_or_else(|| DEFAULT_KEYDIR.into());
+
+    rejson::load_private_key(secrets_file, &keydir).context(Failure::MissingKey)
+}
+
+/// Parses a duration supplied on the command line as a number of hours, days or weeks (e.g. 30d).
+fn parse_duration(s: &str) -> Result<Duration> {
+    let (count, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        _ => return Err(anyhow::anyhow!("expected a duration like 12h, 30d or 4w, got {}", s)),
+    };
+
+    count
+        .parse::<u64>()
+        .ok()
+        .and_then(|count| count.checked_mul(hours * 60 * 60))
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow::anyhow!("expected a duration like 12h, 30d or 4w, got {}", s))
+}
+
+/// Formats the duration the way [parse_duration] expects it, in days (or hours when it isn't a
+/// whole number of days).
+fn format_duration(duration: Duration) -> String {
+    let hours = duration.as_secs() / 3600;
+    match hours % 24 {
+        0 => format!("{}d", hours / 24),
+        _ => format!("{}h", hours),
+    }
+}
+
+/// Parses a KEY=VALUE pair supplied on the command line.
+fn parse_key_value(s: &str) -> Result<(String, String)> {
+    s.split_once('=')
+        .filter(|(k, _)| !k.is_empty())
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| anyhow::anyhow!("expected KEY=VALUE, got {}", s))
+}
+
+#[test]
+fn verify_cli() {
+    use clap::CommandFactory;
+    Cli::command().debug_assert()
+}
@@ -0,0 +1,268 @@
+use std::{collections::BTreeSet, fmt, process::ExitCode};
+
+use rejson::Key;
+use serde_json::{Value, json};
+
+/// Why a command failed, which determines its exit status (see [crate::EXIT_CODES]). Attach one
+/// to an error with [anyhow::Context::context]; errors without one exit with [GENERAL_FAILURE].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Usage,
+    MissingKey,
+    Decryption,
+    Validation,
+}
+
+/// The exit status of failures without a [Failure].
+const GENERAL_FAILURE: u8 = 1;
+
+impl Failure {
+    /// Returns the exit status.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Usage => 2,
+            Self::MissingKey => 3,
+            Self::Decryption => 4,
+            Self::Validation => 5,
+        }
+    }
+
+    /// Returns the name used in JSON output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Usage => "usage",
+            Self::MissingKey => "missing_key",
+            Self::Decryption => "decryption",
+            Self::Validation => "validation",
+        }
+    }
+
+    /// Returns the failure attached to the error, if any.
+    fn of(error: &anyhow::Error) -> Option<Self> {
+        error.downcast_ref::<Self>().copied()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Usage => "usage error",
+            Self::MissingKey => "missing private key",
+            Self::Decryption => "decryption failed",
+            Self::Validation => "validation failed",
+        })
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// What happened to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Written,
+    Unchanged,
+    Removed,
+    Failed,
+    Skipped,
+}
+
+impl FileStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Written => "written",
+            Self::Unchanged => "unchanged",
+            Self::Removed => "removed",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// Collects the outcome of a command. For people, output is printed as it happens (and the methods
+/// that only record things do nothing), while with `--output json` nothing is printed until
+/// [Report::finish] writes a single JSON object to stdout:
+///
+/// ```json
+/// {
+///   "ok": false,
+///   "exit_code": 4,
+///   "files": [{ "path": "secrets.ejson", "status": "written", "bytes": 123 }],
+///   "values": { "encrypted": 2, "skipped": 1 },
+///   "fingerprints": ["1a2b3c4d"],
+///   "warnings": [{ "file": "secrets.ejson", "key": "token", "message": "..." }],
+///   "errors": [{ "file": "secrets.ejson", "key": "password", "kind": "decryption", "message": "..." }],
+///   "result": null
+/// }
+/// ```
+///
+/// `values` is only set by encrypt, and `result` holds the command's output (e.g. the decrypted
+/// file, or the findings of check).
+pub struct Report {
+    json: bool,
+    files: Vec<Value>,
+    values: Option<(usize, usize)>,
+    fingerprints: BTreeSet<String>,
+    warnings: Vec<Value>,
+    errors: Vec<Value>,
+    result: Value,
+    subject: Option<String>,
+}
+
+impl Report {
+    pub fn new(json: bool) -> Self {
+        Self {
+            json,
+            files: vec![],
+            values: None,
+            fingerprints: BTreeSet::new(),
+            warnings: vec![],
+            errors: vec![],
+            result: Value::Null,
+            subject: None,
+        }
+    }
+
+    /// Sets the file a single-file command operates on, which the command's error (if any) is
+    /// reported against.
+    pub fn subject<S: Into<String>>(&mut self, file: Option<S>) {
+        self.subject = file.map(Into::into);
+    }
+
+    /// Returns whether the output is JSON.
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// Prints a line to stdout, unless the output is JSON.
+    pub fn println<D: fmt::Display>(&self, line: D) {
+        if !self.json {
+            println!("{}", line);
+        }
+    }
+
+    /// Prints to stdout (without adding a new line), unless the output is JSON.
+    pub fn print<D: fmt::Display>(&self, s: D) {
+        if !self.json {
+            print!("{}", s);
+        }
+    }
+
+    /// Records a file and what happened to it.
+    pub fn file<D: fmt::Display>(&mut self, path: D, status: FileStatus, bytes: Option<usize>) {
+        let mut file = json!({ "path": path.to_string(), "status": status.as_str() });
+        if let Some(bytes) = bytes {
+            file["bytes"] = bytes.into();
+        }
+
+        self.files.push(file);
+    }
+
+    /// Records a written file, printing "Wrote N bytes to PATH" (or "Wrote PATH" without a size).
+    pub fn wrote<D: fmt::Display>(&mut self, path: D, bytes: Option<usize>) {
+        match bytes {
+            Some(bytes) => self.println(format!("Wrote {} bytes to {}", bytes, path)),
+            None => self.println(format!("Wrote {}", path)),
+        }
+
+        self.file(path, FileStatus::Written, bytes);
+    }
+
+    /// Records a removed file, printing "Removed PATH".
+    pub fn removed<D: fmt::Display>(&mut self, path: D) {
+        self.println(format!("Removed {}", path));
+        self.file(path, FileStatus::Removed, None);
+    }
+
+    /// Records a file that couldn't be processed, printing the error to stderr.
+    pub fn failed<D: fmt::Display>(&mut self, path: D, error: &anyhow::Error) {
+        if !self.json {
+            eprintln!("{}: {:#}", path, error);
+        }
+
+        self.error(
+            Some(&path.to_string()),
+            None,
+            Failure::of(error),
+            format!("{:#}", error),
+        );
+        self.file(path, FileStatus::Failed, None);
+    }
+
+    /// Adds to the number of values that were encrypted and that were skipped (already encrypted).
+    pub fn values(&mut self, encrypted: usize, skipped: usize) {
+        let (total_encrypted, total_skipped) = self.values.unwrap_or_default();
+        self.values = Some((total_encrypted + encrypted, total_skipped + skipped));
+    }
+
+    /// Records the fingerprint of a public key that was used.
+    pub fn fingerprint(&mut self, public_key: Option<Key>) {
+        if let Some(key) = public_key {
+            self.fingerprints.insert(key.fingerprint());
+        }
+    }
+
+    /// Records a warning, printing its message to stderr.
+    pub fn warning<S: Into<String>>(&mut self, file: Option<&str>, key: Option<&str>, message: S) {
+        let message = message.into();
+        if !self.json {
+            eprintln!("{}", message);
+        }
+
+        self.warnings
+            .push(json!({ "file": file, "key": key, "message": message }));
+    }
+
+    /// Records an error (without printing it), e.g. one of several problems found in a file.
+    pub fn error<S: Into<String>>(&mut self, file: Option<&str>, key: Option<&str>, kind: Option<Failure>, message: S) {
+        self.errors.push(json!({
+            "file": file,
+            "key": key,
+            "kind": kind.map_or("error", |kind| kind.as_str()),
+            "message": message.into(),
+        }));
+    }
+
+    /// Sets the result of the command.
+    pub fn result(&mut self, result: Value) {
+        self.result = result;
+    }
+
+    /// Reports how the command finished, printing its error (or the JSON report), and returns the
+    /// exit status.
+    pub fn finish(mut self, outcome: anyhow::Result<()>) -> ExitCode {
+        let code = match &outcome {
+            Ok(_) => 0,
+            Err(e) => Failure::of(e).map_or(GENERAL_FAILURE, |failure| failure.code()),
+        };
+
+        if let Err(e) = &outcome {
+            match self.json {
+                true => {
+                    let subject = self.subject.take();
+                    self.error(subject.as_deref(), None, Failure::of(e), format!("{:#}", e))
+                }
+                false => eprintln!("Error: {:?}", e),
+            }
+        }
+
+        if self.json {
+            let values = self
+                .values
+                .map(|(encrypted, skipped)| json!({ "encrypted": encrypted, "skipped": skipped }));
+            let report = json!({
+                "ok": outcome.is_ok(),
+                "exit_code": code,
+                "files": self.files,
+                "values": values,
+                "fingerprints": self.fingerprints,
+                "warnings": self.warnings,
+                "errors": self.errors,
+                "result": self.result,
+            });
+
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        }
+
+        ExitCode::from(code)
+    }
+}
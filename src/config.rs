@@ -0,0 +1,376 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use globset::{GlobBuilder, GlobMatcher};
+use serde_json::{Value, json};
+
+use crate::Key;
+
+/// The name of the project configuration file.
+pub const CONFIG_FILE: &str = ".rejson.toml";
+
+/// The default key of the values exported by `env`.
+pub const DEFAULT_ENV_KEY: &str = "environment";
+
+/// The default key of the secrets generated by `kube-secrets`.
+pub const DEFAULT_KUBE_SECRETS_KEY: &str = "kubernetes";
+
+const SETTINGS: [&str; 9] = [
+    "keydir",
+    "key_env",
+    "include",
+    "exclude",
+    "env_key",
+    "kube_secrets_key",
+    "output",
+    "formats",
+    "rules",
+];
+const RULE_SETTINGS: [&str; 3] = ["path", "public_key", "keydir"];
+
+/// Project settings, read from the [CONFIG_FILE] in a directory or the closest of its parents.
+///
+/// ```toml
+/// keydir = "keys"                 # relative to the config file
+/// key_env = "EJSON_PRIVATE_KEY"   # used instead of the keydir when it's set (but not a rule's keydir)
+/// include = ["*.ejson"]
+/// exclude = ["vendor/**"]
+/// env_key = "environment"
+/// kube_secrets_key = "kubernetes"
+/// output = "human"
+///
+/// [formats]
+/// check = "sarif"
+///
+/// [[rules]]
+/// path = "services/payments/**"   # relative to the config file
+/// public_key = "..."
+/// keydir = "/opt/payments/keys"
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The file the settings were read from, if any.
+    pub path: Option<PathBuf>,
+    /// The directory to load private keys from (`keydir`).
+    pub keydir: Option<String>,
+    /// The name of an environment variable holding the private key (`key_env`).
+    pub key_env: Option<String>,
+    /// The files included when walking directories (`include`).
+    pub include: Vec<String>,
+    /// The files excluded when walking directories (`exclude`).
+    pub exclude: Vec<String>,
+    /// The key of the values exported by `env` (`env_key`).
+    pub env_key: String,
+    /// The key of the secrets generated by `kube-secrets` (`kube_secrets_key`).
+    pub kube_secrets_key: String,
+    /// The default `--output` format (`output`).
+    pub output: Option<String>,
+    /// The default `--format` of commands, by command name (`formats`).
+    pub formats: BTreeMap<String, String>,
+    /// Settings for the files under particular paths, in the order they're defined (`rules`).
+    pub rules: Vec<PathRule>,
+}
+
+/// Settings for the files matching a glob.
+#[derive(Debug, Clone)]
+pub struct PathRule {
+    /// The glob, relative to the config file (`path`).
+    pub path: String,
+    /// The public key new files get (`public_key`).
+    pub public_key: Option<Key>,
+    /// The directory to load private keys from (`keydir`).
+    pub keydir: Option<String>,
+    matcher: GlobMatcher,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: None,
+            keydir: None,
+            key_env: None,
+            include: vec![],
+            exclude: vec![],
+            env_key: DEFAULT_ENV_KEY.into(),
+            kube_secrets_key: DEFAULT_KUBE_SECRETS_KEY.into(),
+            output: None,
+            formats: BTreeMap::new(),
+            rules: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Reads the [CONFIG_FILE] in the directory or the closest of its parents, returning the
+    /// defaults when there isn't one.
+    pub fn discover<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = std::path::absolute(dir.as_ref())?;
+        match dir.ancestors().map(|d| d.join(CONFIG_FILE)).find(|p| p.is_file()) {
+            Some(path) => Self::load(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Reads the settings from the file. Relative paths are resolved against its directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = std::path::absolute(path.as_ref())?;
+        let contents = fs::read_to_string(&path)?;
+        let value: Value = toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("/"));
+
+        Self::from_value(&value, dir)
+            .map(|config| Self {
+                path: Some(path.clone()),
+                ..config
+            })
+            .with_context(|| format!("reading {}", path.display()))
+    }
+
+    fn from_value(value: &Value, dir: &Path) -> Result<Self> {
+        check_settings(value, &SETTINGS, "")?;
+        let defaults = Self::default();
+        let resolve = |s: String| dir.join(s).display().to_string();
+
+        let rules = match &value["rules"] {
+            Value::Null => vec![],
+            Value::Array(rules) => rules
+                .iter()
+                .enumerate()
+                .map(|(i, rule)| PathRule::from_value(rule, dir).with_context(|| format!("rules[{}]", i)))
+                .collect::<Result<_>>()?,
+            _ => return Err(anyhow!("rules must be an array of tables")),
+        };
+
+        Ok(Self {
+            path: None,
+            keydir: string(value, "keydir")?.map(resolve),
+            key_env: string(value, "key_env")?,
+            include: strings(value, "include")?,
+            exclude: strings(value, "exclude")?,
+            env_key: string(value, "env_key")?.unwrap_or(defaults.env_key),
+            kube_secrets_key: string(value, "kube_secrets_key")?.unwrap_or(defaults.kube_secrets_key),
+            output: string(value, "output")?,
+            formats: string_map(value, "formats")?,
+            rules,
+        })
+    }
+
+    /// Returns the directory containing the config file, if any.
+    pub fn dir(&self) -> Option<&Path> {
+        self.path.as_deref().and_then(Path::parent)
+    }
+
+    /// Returns the rules whose globs match the file, in the order they're defined.
+    pub fn rules_for<P: AsRef<Path>>(&self, file: P) -> impl Iterator<Item = &PathRule> {
+        let relative = self.dir().and_then(|dir| {
+            let file = std::path::absolute(file.as_ref()).ok()?;
+            file.strip_prefix(dir).ok().map(Path::to_path_buf)
+        });
+
+        self.rules
+            .iter()
+            .filter(move |rule| relative.as_ref().is_some_and(|path| rule.matcher.is_match(path)))
+    }
+
+    /// Returns the keydir for the file: the keydir of the first matching rule that has one, or the
+    /// `keydir` setting.
+    pub fn keydir_for<P: AsRef<Path>>(&self, file: P) -> Option<&str> {
+        self.rules_for(file)
+            .find_map(|rule| rule.keydir.as_deref())
+            .or(self.keydir.as_deref())
+    }
+
+    /// Returns the public key new files at the path get, from the first matching rule that has one.
+    pub fn public_key_for<P: AsRef<Path>>(&self, file: P) -> Option<&Key> {
+        self.rules_for(file).find_map(|rule| rule.public_key.as_ref())
+    }
+
+    /// Returns the settings as JSON, in the same form as the config file (with the `path` it was
+    /// read from).
+    pub fn to_json(&self) -> Value {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                json!({
+                    "path": rule.path,
+                    "public_key": rule.public_key.as_ref().map(Key::to_string),
+                    "keydir": rule.keydir,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "path": self.path,
+            "keydir": self.keydir,
+            "key_env": self.key_env,
+            "include": self.include,
+            "exclude": self.exclude,
+            "env_key": self.env_key,
+            "kube_secrets_key": self.kube_secrets_key,
+            "output": self.output,
+            "formats": self.formats,
+            "rules": rules,
+        })
+    }
+}
+
+impl PathRule {
+    fn from_value(value: &Value, dir: &Path) -> Result<Self> {
+        check_settings(value, &RULE_SETTINGS, "rules.")?;
+
+        let path = string(value, "path")?.ok_or_else(|| anyhow!("path is required"))?;
+        let matcher = GlobBuilder::new(path.trim_start_matches("./"))
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        let public_key = string(value, "public_key")?
+            .map(|key| key.parse().context("invalid public_key"))
+            .transpose()?;
+
+        Ok(Self {
+            path,
+            public_key,
+            keydir: string(value, "keydir")?.map(|s| dir.join(s).display().to_string()),
+            matcher,
+        })
+    }
+}
+
+/// Fails on any setting that isn't known, to catch typos.
+fn check_settings(value: &Value, known: &[&str], prefix: &str) -> Result<()> {
+    let table = value.as_object().ok_or_else(|| anyhow!("expected a table"))?;
+    match table.keys().find(|k| !known.contains(&k.as_str())) {
+        Some(key) => Err(anyhow!("unknown setting {}{}", prefix, key)),
+        None => Ok(()),
+    }
+}
+
+fn string(value: &Value, key: &str) -> Result<Option<String>> {
+    match &value[key] {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.to_string())),
+        _ => Err(anyhow!("{} must be a string", key)),
+    }
+}
+
+fn strings(value: &Value, key: &str) -> Result<Vec<String>> {
+    match &value[key] {
+        Value::Null => Ok(vec![]),
+        Value::String(s) => Ok(vec![s.to_string()]),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| v.as_str().map(String::from))
+            .collect::<Option<_>>()
+            .ok_or_else(|| anyhow!("{} must be a list of strings", key)),
+        _ => Err(anyhow!("{} must be a list of strings", key)),
+    }
+}
+
+fn string_map(value: &Value, key: &str) -> Result<BTreeMap<String, String>> {
+    match &value[key] {
+        Value::Null => Ok(BTreeMap::new()),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| v.as_str().map(|v| (k.to_string(), v.to_string())))
+            .collect::<Option<_>>()
+            .ok_or_else(|| anyhow!("{} values must be strings", key)),
+        _ => Err(anyhow!("{} must be a table", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+
+    #[test]
+    fn discover() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let nested = dir.path().join("services/api");
+        fs::create_dir_all(&nested)?;
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            format!(
+                r#"
+keydir = "keys"
+include = "*.json"
+env_key = "env"
+
+[formats]
+check = "sarif"
+
+[[rules]]
+path = "services/payments/**"
+keydir = "/opt/payments/keys"
+
+[[rules]]
+path = "services/**"
+public_key = "{}"
+"#,
+                PUB_KEY
+            ),
+        )?;
+
+        let config = Config::discover(&nested)?;
+        assert_eq!(Some(dir.path().join(CONFIG_FILE)), config.path);
+        assert_eq!(Some(dir.path().join("keys").display().to_string()), config.keydir);
+        assert_eq!(vec!["*.json".to_string()], config.include);
+        assert_eq!("env", config.env_key);
+        assert_eq!(DEFAULT_KUBE_SECRETS_KEY, config.kube_secrets_key);
+        assert_eq!(Some(&"sarif".to_string()), config.formats.get("check"));
+
+        let payments = dir.path().join("services/payments/secrets.ejson");
+        let api = nested.join("secrets.ejson");
+        assert_eq!(Some("/opt/payments/keys"), config.keydir_for(&payments));
+        assert_eq!(Some(&PUB_KEY.parse()?), config.public_key_for(&payments));
+        assert_eq!(config.keydir.as_deref(), config.keydir_for(&api));
+        assert_eq!(Some(&PUB_KEY.parse()?), config.public_key_for(&api));
+        assert_eq!(
+            config.keydir.as_deref(),
+            config.keydir_for(dir.path().join("secrets.ejson"))
+        );
+
+        let json = config.to_json();
+        assert_eq!(PUB_KEY, json["rules"][1]["public_key"]);
+        assert_eq!(Value::Null, json["key_env"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn discover_defaults() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let config = Config::discover(dir.path())?;
+        assert_eq!(None, config.path);
+        assert_eq!(DEFAULT_ENV_KEY, config.env_key);
+        assert_eq!(None, config.keydir_for(dir.path().join("secrets.ejson")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let path = dir.path().join(CONFIG_FILE);
+        let cases = [
+            ("kedir = \"keys\"", "unknown setting kedir"),
+            ("include = 1", "include must be a list of strings"),
+            ("[[rules]]\nkeydir = \"keys\"", "path is required"),
+            ("[[rules]]\npath = \"*\"\npublic_key = \"nope\"", "invalid public_key"),
+            ("[[rules]]\npath = \"*\"\nkey = \"nope\"", "unknown setting rules.key"),
+        ];
+
+        cases.into_iter().try_for_each(|(contents, want)| -> Result<()> {
+            fs::write(&path, contents)?;
+            let err = format!("{:#}", Config::load(&path).unwrap_err());
+            assert!(err.contains(want), "{} should contain {}", err, want);
+            Ok(())
+        })
+    }
+}
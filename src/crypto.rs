@@ -3,5 +3,6 @@ mod encryptor;
 mod keys;
 mod message;
 
+pub(crate) use decryptor::Decryptor;
 pub use keys::{Key, KeyPair};
 pub(crate) use message::Message;
@@ -1,18 +1,33 @@
+use std::{collections::VecDeque, sync::Mutex};
+
 use anyhow::Result;
-use nacl::public_box;
+use nacl::{public_box, secret_box};
+use zeroize::Zeroize;
+
+use super::{
+    keys::{Key, KeyPair},
+    message::Message,
+};
 
-use super::{keys::KeyPair, message::Message};
+/// The maximum number of shared keys kept by a [Decryptor]. Values encrypted in the same run share
+/// an ephemeral key, so a file rarely needs more than a handful.
+const CACHE_SIZE: usize = 64;
 
 /// A struct for managing the decryption of serialized messages into their original plain text
 /// strings.
 pub struct Decryptor {
     keys: KeyPair,
+    /// The (DH) shared keys for recently seen ephemeral public keys, oldest first.
+    shared_keys: Mutex<VecDeque<(Key, Key)>>,
 }
 
 impl Decryptor {
     /// Creates a new [Decryptor] for the given [KeyPair].
     pub fn new(keys: KeyPair) -> Self {
-        Self { keys }
+        Self {
+            keys,
+            shared_keys: Mutex::new(VecDeque::with_capacity(CACHE_SIZE)),
+        }
     }
 
     /// Decrypts the given ciphertext into the original plaintext. The ciphertext is expected to be
@@ -21,18 +36,42 @@ impl Decryptor {
     /// If the given [KeyPair] is not the same that was used to encrypt the ciphertext, as you
     /// might expect this will return an error.
     ///
-    /// NB: Unlike encryption, decryption does not required a shared key.
+    /// The shared key for each ephemeral public key is only calculated once (for the most recent
+    /// [CACHE_SIZE] keys), since every value encrypted in the same run uses the same one.
     pub fn decrypt<S: AsRef<str>>(&self, ciphertext: S) -> Result<String> {
+        Ok(String::from_utf8(self.decrypt_bytes(ciphertext)?)?)
+    }
+
+    /// Decrypts the given ciphertext into the original plaintext bytes. Unlike
+    /// [Decryptor::decrypt], the plaintext is not required to be valid UTF-8.
+    pub fn decrypt_bytes<S: AsRef<str>>(&self, ciphertext: S) -> Result<Vec<u8>> {
         let message: Message = ciphertext.as_ref().parse()?;
-        let plaintext = public_box::open(
-            message.value.as_slice(),
-            &message.nonce.0,
-            &message.key.0,
-            &self.keys.private.0,
-        )
-        .map_err(|e| anyhow::anyhow!(e.message))?;
-
-        Ok(String::from_utf8(plaintext)?)
+        let shared_key = self.shared_key(&message.key)?;
+
+        secret_box::open(message.value.as_slice(), &message.nonce.0, &shared_key.0)
+            .map_err(|e| anyhow::anyhow!(e.message))
+    }
+
+    /// Returns the shared key for the ephemeral public key, calculating (and caching) it if needed.
+    fn shared_key(&self, peer_public: &Key) -> Result<Key> {
+        let mut cache = self.shared_keys.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, shared_key)) = cache.iter().find(|(k, _)| k == peer_public) {
+            return Ok(shared_key.clone());
+        }
+
+        let mut bytes = public_box::calc_dhshared_key(&peer_public.0, &self.keys.private.0)
+            .map_err(|e| anyhow::anyhow!(e.message))?;
+        let shared_key = bytes.as_slice().try_into().map(Key);
+        bytes.zeroize();
+        let shared_key = shared_key?;
+
+        // Evicted (and, when the decryptor is dropped, cached) keys wipe themselves.
+        if cache.len() == CACHE_SIZE {
+            cache.pop_front();
+        }
+
+        cache.push_back((peer_public.clone(), shared_key.clone()));
+        Ok(shared_key)
     }
 }
 
@@ -52,4 +91,46 @@ mod tests {
         let ciphertext = encryptor.encrypt(plaintext).unwrap();
         assert_eq!(plaintext, decryptor.decrypt(ciphertext).unwrap());
     }
+
+    #[test]
+    fn decrypt_bytes() {
+        let durable_key = KeyPair::generate().unwrap();
+        let emphemeral_key = KeyPair::generate().unwrap();
+
+        let encryptor = emphemeral_key.encryptor(durable_key.public.clone()).unwrap();
+        let decryptor = durable_key.decryptor();
+
+        let plaintext = [0xde, 0xad, 0xbe, 0xef, 0xff];
+        let ciphertext = encryptor.encrypt_bytes(plaintext).unwrap();
+        assert_eq!(plaintext.to_vec(), decryptor.decrypt_bytes(&ciphertext).unwrap());
+        assert!(decryptor.decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn shared_key_cache() {
+        let durable_key = KeyPair::generate().unwrap();
+        let decryptor = durable_key.decryptor();
+        let encrypt = |plaintext: &str| {
+            let encryptor = KeyPair::generate()
+                .unwrap()
+                .encryptor(durable_key.public.clone())
+                .unwrap();
+            encryptor.encrypt(plaintext).unwrap()
+        };
+
+        let ciphertext = encrypt("first");
+        assert_eq!("first", decryptor.decrypt(&ciphertext).unwrap());
+        assert_eq!("first", decryptor.decrypt(&ciphertext).unwrap());
+        assert_eq!(1, decryptor.shared_keys.lock().unwrap().len());
+
+        // Old keys are evicted, but can still be decrypted.
+        (0..CACHE_SIZE).for_each(|i| {
+            assert_eq!(i.to_string(), decryptor.decrypt(encrypt(&i.to_string())).unwrap());
+        });
+        assert_eq!(CACHE_SIZE, decryptor.shared_keys.lock().unwrap().len());
+        assert_eq!("first", decryptor.decrypt(&ciphertext).unwrap());
+
+        // Values encrypted for a different key pair still fail.
+        assert!(KeyPair::generate().unwrap().decryptor().decrypt(&ciphertext).is_err());
+    }
 }
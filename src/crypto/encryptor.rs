@@ -34,13 +34,19 @@ impl Encryptor {
 
     /// Encrypts the given string returning the value to be stored in the EJSON file.
     pub fn encrypt<S: Into<String>>(&self, plaintext: S) -> Result<String> {
+        self.encrypt_bytes(plaintext.into())
+    }
+
+    /// Encrypts the given bytes returning the value to be stored in the EJSON file. The plaintext
+    /// does not need to be valid UTF-8.
+    pub fn encrypt_bytes<B: AsRef<[u8]>>(&self, plaintext: B) -> Result<String> {
         let nonce = Nonce::random();
-        let value = secret_box::pack(plaintext.into().as_bytes(), &nonce.0, &self.shared_key.0)
+        let value = secret_box::pack(plaintext.as_ref(), &nonce.0, &self.shared_key.0)
             .map_err(|e| anyhow::anyhow!(e.message))?;
 
         // Box the message and return in EJSON format
         Ok(Message {
-            version: 1,
+            version: Message::VERSION,
             key: self.keys.public.clone(),
             nonce,
             value,
@@ -3,6 +3,7 @@ use std::{fmt, fs, path::Path, str::FromStr};
 use anyhow::{Result, anyhow};
 use nacl::public_box;
 use rand::RngCore;
+use zeroize::Zeroize;
 
 use super::{decryptor::Decryptor, encryptor::Encryptor};
 
@@ -11,10 +12,17 @@ use super::{decryptor::Decryptor, encryptor::Encryptor};
 const KEY_SIZE: usize = 32;
 const NONCE_SIZE: usize = 24;
 
-/// A newtype representing an encryption key (32-byte array)
+/// A newtype representing an encryption key (32-byte array). The bytes are wiped when it's dropped,
+/// since private and shared keys are keys too.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Key(pub(crate) [u8; KEY_SIZE]);
 
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl Key {
     /// Reads in a [Key] from the supplied path.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -33,6 +41,12 @@ impl Key {
         rand::rng().fill_bytes(&mut bytes);
         Self(bytes)
     }
+
+    /// Returns a short identifier of the key (the first few bytes of its SHA-512), for telling keys
+    /// apart in reports.
+    pub fn fingerprint(&self) -> String {
+        crate::short_hash(&self.0)
+    }
 }
 
 impl fmt::Display for Key {
@@ -50,17 +64,17 @@ impl FromStr for Key {
             return Err(anyhow!("InvalidKey (bad length)"));
         }
 
-        Ok(Self(bytes_from_hex(s).as_slice().try_into()?))
+        Ok(Self(bytes_from_hex(s)?.as_slice().try_into()?))
     }
 }
 
-fn bytes_from_hex(s: &str) -> Vec<u8> {
+fn bytes_from_hex(s: &str) -> Result<Vec<u8>> {
     (0..s.len())
         .step_by(2)
         .map(|i| {
             s.get(i..i + 2)
                 .and_then(|sub| u8::from_str_radix(sub, 16).ok())
-                .unwrap()
+                .ok_or_else(|| anyhow!("InvalidKey (bad hex)"))
         })
         .collect()
 }
@@ -106,10 +120,13 @@ impl KeyPair {
 
     /// Generates a new [KeyPair] using a randomly generated private key.
     pub fn generate() -> Result<Self> {
-        // Generate a random private key. Then using the private key, generate a corresponding public key.
-        let priv_key = Key::random();
-        let pub_key = public_box::generate_pubkey(&priv_key.0).map_err(|e| anyhow!(e.message))?;
-        Ok(Self::new(Key(pub_key.as_slice().try_into()?), priv_key))
+        Self::from_private(Key::random())
+    }
+
+    /// Creates a [KeyPair] from a private key, deriving the corresponding public key.
+    pub fn from_private(private: Key) -> Result<Self> {
+        let pub_key = public_box::generate_pubkey(&private.0).map_err(|e| anyhow!(e.message))?;
+        Ok(Self::new(Key(pub_key.as_slice().try_into()?), private))
     }
 
     /// Returns the hex encoded public key.
@@ -155,6 +172,9 @@ mod tests {
 
         let parsed = key_str.parse().unwrap();
         assert_eq!(key, parsed);
+
+        assert!("nope".parse::<Key>().is_err());
+        assert!("zz".repeat(KEY_SIZE).parse::<Key>().is_err());
     }
 
     #[test]
@@ -176,4 +196,12 @@ mod tests {
         assert_eq!(2 * KEY_SIZE, pair.public_key().len());
         assert_eq!(2 * KEY_SIZE, pair.private_key().len());
     }
+
+    #[test]
+    fn from_private() {
+        let pair = KeyPair::generate().unwrap();
+        let derived = KeyPair::from_private(pair.private.clone()).unwrap();
+        assert_eq!(pair.public, derived.public);
+        assert_eq!(pair.private, derived.private);
+    }
 }
@@ -28,6 +28,9 @@ pub(crate) struct Message {
 }
 
 impl Message {
+    /// The version of the message format that is written (and supported).
+    pub const VERSION: u8 = 1;
+
     /// Returns whether or not the supplied string is an EJSON encoded message.
     pub fn is_valid(encoded_str: &str) -> bool {
         PATTERN.is_match(encoded_str)
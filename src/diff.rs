@@ -0,0 +1,162 @@
+use std::{collections::BTreeSet, fmt};
+
+use rand::RngCore;
+use serde_json::{Value, json};
+
+use crate::{Change, SecretsMap, json::PK_KEY, keyed_hash};
+
+/// The differences between the (flattened) keys and values of two secrets files, sorted by key.
+///
+/// ```
+/// use rejson::{SecretsDiff, SecretsMap};
+/// use serde_json::json;
+///
+/// let from: SecretsMap = json!({ "a": "1", "b": "2" }).into();
+/// let to: SecretsMap = json!({ "a": "1", "b": "3", "c": "4" }).into();
+///
+/// let diff = SecretsDiff::new(&from, &to);
+/// assert_eq!("~ b (2 -> 3)\n+ c (4)\n", diff.to_string());
+/// ```
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SecretsDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+/// A key that was added, removed or changed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub key: String,
+    pub change: Change,
+    /// The previous value, if any.
+    pub from: Option<String>,
+    /// The new value, if any.
+    pub to: Option<String>,
+}
+
+impl SecretsDiff {
+    /// Compares the values of the two maps. The public keys are ignored since they're expected to
+    /// differ between environments.
+    pub fn new(from: &SecretsMap, to: &SecretsMap) -> Self {
+        let entries = from
+            .keys()
+            .chain(to.keys())
+            .filter(|key| *key != PK_KEY)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|key| {
+                let (from, to) = (from.get(key), to.get(key));
+                let change = match (from, to) {
+                    (Some(from), Some(to)) if from == to => return None,
+                    (Some(_), Some(_)) => Change::Changed,
+                    (Some(_), None) => Change::Removed,
+                    _ => Change::Added,
+                };
+
+                Some(DiffEntry {
+                    key: key.to_string(),
+                    change,
+                    from: from.cloned(),
+                    to: to.cloned(),
+                })
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Returns whether there are no differences.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Replaces every value with a short keyed hash (HMAC-SHA512) of it, using a random key that's
+    /// only used for this diff. Identical values still have identical hashes, but the hashes can't
+    /// be compared to anything outside of this diff.
+    pub fn masked(mut self) -> Self {
+        let mut key = [0u8; 32];
+        rand::rng().fill_bytes(&mut key);
+
+        self.entries.iter_mut().for_each(|entry| {
+            entry.from = entry.from.as_ref().map(|v| keyed_hash(&key, v.as_bytes()));
+            entry.to = entry.to.as_ref().map(|v| keyed_hash(&key, v.as_bytes()));
+        });
+
+        self
+    }
+
+    /// Returns the differences as JSON.
+    ///
+    /// ```json
+    /// { "entries": [{ "key": "b", "change": "changed", "from": "2", "to": "3" }] }
+    /// ```
+    pub fn to_json(&self) -> Value {
+        let entries = self
+            .entries
+            .iter()
+            .map(|e| json!({ "key": e.key, "change": e.change.as_str(), "from": e.from, "to": e.to }))
+            .collect::<Vec<_>>();
+
+        json!({ "entries": entries })
+    }
+}
+
+impl fmt::Display for SecretsDiff {
+    /// Writes a line per key with its value(s).
+    ///
+    /// ```text
+    /// + added (value)
+    /// ~ changed (old -> new)
+    /// - removed (value)
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.entries
+            .iter()
+            .try_for_each(|entry| match (&entry.from, &entry.to) {
+                (Some(from), Some(to)) => writeln!(f, "{} {} ({} -> {})", entry.change, entry.key, from, to),
+                (Some(value), None) | (None, Some(value)) => {
+                    writeln!(f, "{} {} ({})", entry.change, entry.key, value)
+                }
+                (None, None) => writeln!(f, "{} {}", entry.change, entry.key),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let from: SecretsMap = json!({ "_public_key": "a", "same": "1", "changed": "2", "removed": "3" }).into();
+        let to: SecretsMap =
+            json!({ "_public_key": "b", "same": "1", "changed": "4", "nested": { "added": "5" } }).into();
+
+        let diff = SecretsDiff::new(&from, &to);
+        assert_eq!(
+            "~ changed (2 -> 4)\n+ nested.added (5)\n- removed (3)\n",
+            diff.to_string()
+        );
+        assert_eq!(
+            json!({ "key": "removed", "change": "removed", "from": "3", "to": null }),
+            diff.to_json()["entries"][2]
+        );
+
+        assert!(SecretsDiff::new(&from, &from).is_empty());
+    }
+
+    #[test]
+    fn masked() {
+        let from: SecretsMap = json!({ "a": "secret", "b": "other" }).into();
+        let to: SecretsMap = json!({ "a": "changed", "b": "secret" }).into();
+
+        let diff = SecretsDiff::new(&from, &to).masked();
+        let output = diff.to_string();
+        assert!(!output.contains("secret") && !output.contains("changed") && !output.contains("other"));
+
+        // The same value has the same hash within a diff.
+        assert_eq!(diff.entries[0].from, diff.entries[1].to);
+        assert_ne!(diff.entries[0].from, diff.entries[0].to);
+    }
+}
@@ -0,0 +1,517 @@
+use std::{
+    fmt,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+
+use crate::{Key, KeyPair, SecretsFile, Severity};
+
+/// The length of a hex encoded key.
+const KEY_LENGTH: usize = 64;
+
+/// The results of inspecting a keydir and the secrets files that rely on it. Private keys are never
+/// kept or reported.
+#[derive(Debug, Default)]
+pub struct Diagnosis {
+    pub keydir: String,
+    /// The public keys with a usable private key in the keydir (and the keydirs of [KeySource]s).
+    pub keys: Vec<Key>,
+    /// The secrets files that were inspected.
+    pub files: Vec<DiagnosedFile>,
+    /// The problems found, keydir first, then key files (by name) and secrets files.
+    pub problems: Vec<Diagnostic>,
+}
+
+/// A secrets file and whether it can be decrypted with the keys in the keydir.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DiagnosedFile {
+    pub path: String,
+    /// The file's public key, when it's valid.
+    pub public_key: Option<Key>,
+    /// Whether the file's key source has a usable private key for it.
+    pub key_available: bool,
+}
+
+/// Where the private key for a secrets file is looked up, when it isn't the keydir.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// Another keydir (e.g. from a matching rule), which is inspected like the keydir.
+    Keydir(PathBuf),
+    /// An environment variable, with the private key it holds (if it's a valid one).
+    Env(String, Option<Key>),
+}
+
+/// A problem with the keydir, a key file or a secrets file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The path of the keydir or file with the problem.
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+    /// A suggested fix, when there's an obvious one.
+    pub fix: Option<String>,
+}
+
+impl Diagnostic {
+    fn new<P: AsRef<Path>, S: Into<String>>(path: P, severity: Severity, message: S) -> Self {
+        Self {
+            path: path.as_ref().display().to_string(),
+            severity,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn with_fix<S: Into<String>>(mut self, fix: S) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+impl Diagnosis {
+    /// Returns whether no errors were found (warnings are allowed).
+    pub fn is_ok(&self) -> bool {
+        self.problems.iter().all(|p| p.severity == Severity::Warning)
+    }
+
+    /// Returns the diagnosis as JSON.
+    ///
+    /// ```json
+    /// {
+    ///   "keydir": "/opt/ejson/keys",
+    ///   "keys": [{ "public_key": "...", "fingerprint": "1a2b3c4d" }],
+    ///   "files": [{ "path": "secrets.ejson", "public_key": "...", "key_available": true }],
+    ///   "problems": [{ "path": "...", "severity": "warning", "message": "...", "fix": "..." }]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> Value {
+        let keys = self
+            .keys
+            .iter()
+            .map(|k| json!({ "public_key": k.to_string(), "fingerprint": k.fingerprint() }))
+            .collect::<Vec<_>>();
+        let files = self
+            .files
+            .iter()
+            .map(|f| {
+                json!({
+                    "path": f.path,
+                    "public_key": f.public_key.as_ref().map(Key::to_string),
+                    "key_available": f.key_available,
+                })
+            })
+            .collect::<Vec<_>>();
+        let problems = self
+            .problems
+            .iter()
+            .map(|p| json!({ "path": p.path, "severity": p.severity.as_str(), "message": p.message, "fix": p.fix }))
+            .collect::<Vec<_>>();
+
+        json!({ "keydir": self.keydir, "keys": keys, "files": files, "problems": problems })
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    /// Writes a summary of the keydir, a line per file and a line per problem (followed by its fix).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "keydir: {} ({} usable key(s))", self.keydir, self.keys.len())?;
+
+        self.files.iter().try_for_each(|file| {
+            let key = file
+                .public_key
+                .as_ref()
+                .map_or("no public key".into(), Key::fingerprint);
+            match file.key_available {
+                true => writeln!(f, "ok: {}: private key available ({})", file.path, key),
+                false => writeln!(f, "missing: {}: private key not available ({})", file.path, key),
+            }
+        })?;
+
+        self.problems.iter().try_for_each(|problem| {
+            writeln!(
+                f,
+                "{}: {}: {}",
+                problem.severity.as_str(),
+                problem.path,
+                problem.message
+            )?;
+            match &problem.fix {
+                Some(fix) => writeln!(f, "  fix: {}", fix),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/// Inspects the keydir and the secrets files, checking that each file's private key is available,
+/// that key files are only accessible to their owner, contain nothing but a private key, and are
+/// named after the public key derived from it.
+pub fn diagnose<P: AsRef<Path>>(keydir: &Path, files: &[P]) -> Diagnosis {
+    diagnose_with(keydir, files, |_| None)
+}
+
+/// Like [diagnose], but the private key for a file is looked up in the [KeySource] returned for it,
+/// falling back to the keydir when there's none. Other keydirs are inspected after the keydir.
+pub fn diagnose_with<P, F>(keydir: &Path, files: &[P], source_for: F) -> Diagnosis
+where
+    P: AsRef<Path>,
+    F: Fn(&Path) -> Option<KeySource>,
+{
+    let mut diagnosis = Diagnosis {
+        keydir: keydir.display().to_string(),
+        ..Default::default()
+    };
+
+    diagnose_keydir(keydir, &mut diagnosis);
+    let default = KeySource::Keydir(keydir.to_path_buf());
+    let mut sources = vec![(default.clone(), diagnosis.keys.clone())];
+
+    let files = files
+        .iter()
+        .map(|file| {
+            (
+                file.as_ref(),
+                source_for(file.as_ref()).unwrap_or_else(|| default.clone()),
+            )
+        })
+        .collect::<Vec<_>>();
+    for (_, source) in &files {
+        if sources.iter().any(|(s, _)| s == source) {
+            continue;
+        }
+
+        let keys = match source {
+            KeySource::Keydir(dir) => {
+                let known = diagnosis.keys.len();
+                diagnose_keydir(dir, &mut diagnosis);
+                diagnosis.keys[known..].to_vec()
+            }
+            KeySource::Env(_, key) => key
+                .clone()
+                .and_then(|key| KeyPair::from_private(key).ok())
+                .map(|pair| vec![pair.public])
+                .unwrap_or_default(),
+        };
+        sources.push((source.clone(), keys));
+    }
+
+    files.iter().for_each(|(file, source)| {
+        let keys = sources.iter().find(|(s, _)| s == source).map_or(&[][..], |(_, k)| k);
+        diagnose_file(file, source, keys, &mut diagnosis)
+    });
+    diagnosis
+}
+
+fn diagnose_keydir(keydir: &Path, diagnosis: &mut Diagnosis) {
+    let dir = keydir.display();
+    let entries = match fs::metadata(keydir) {
+        Ok(metadata) if !metadata.is_dir() => {
+            let problem = Diagnostic::new(keydir, Severity::Error, "the keydir isn't a directory")
+                .with_fix("set EJSON_KEYDIR (or --keydir) to the directory containing your private keys");
+            return diagnosis.problems.push(problem);
+        }
+        Ok(metadata) => {
+            let mode = metadata.permissions().mode() & 0o777;
+            if mode & 0o022 != 0 {
+                let message = format!("the keydir is writable by other users (mode {:04o})", mode);
+                let problem =
+                    Diagnostic::new(keydir, Severity::Warning, message).with_fix(format!("chmod go-w {}", dir));
+                diagnosis.problems.push(problem);
+            }
+
+            fs::read_dir(keydir)
+        }
+        Err(e) => Err(e),
+    };
+
+    let mut paths = match entries {
+        Ok(entries) => entries.flatten().map(|e| e.path()).collect::<Vec<_>>(),
+        Err(e) => {
+            let problem = Diagnostic::new(keydir, Severity::Error, format!("the keydir can't be read: {}", e))
+                .with_fix(format!(
+                    "create it with `mkdir -p {0} && chmod 700 {0}`, or set EJSON_KEYDIR (or --keydir) to the \
+                     directory containing your private keys",
+                    dir
+                ));
+            return diagnosis.problems.push(problem);
+        }
+    };
+
+    paths.sort();
+    paths
+        .iter()
+        .filter(|p| p.is_file())
+        .filter(|p| !p.file_name().unwrap_or_default().to_string_lossy().starts_with('.'))
+        .for_each(|path| diagnose_key_file(keydir, path, diagnosis));
+}
+
+fn diagnose_key_file(keydir: &Path, path: &Path, diagnosis: &mut Diagnosis) {
+    let Ok(public_key) = path.file_name().unwrap_or_default().to_string_lossy().parse::<Key>() else {
+        let problem = Diagnostic::new(
+            path,
+            Severity::Warning,
+            "isn't named after a public key, so it will never be used",
+        );
+        return diagnosis.problems.push(problem);
+    };
+
+    if let Ok(metadata) = fs::metadata(path) {
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            let message = format!("is accessible to other users (mode {:04o})", mode);
+            let problem =
+                Diagnostic::new(path, Severity::Warning, message).with_fix(format!("chmod 600 {}", path.display()));
+            diagnosis.problems.push(problem);
+        }
+    }
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return diagnosis
+                .problems
+                .push(Diagnostic::new(path, Severity::Error, format!("can't be read: {}", e)));
+        }
+    };
+
+    let private_key = match contents.trim().parse::<Key>() {
+        Ok(key) => key,
+        Err(_) => {
+            let trimmed = contents.trim();
+            let problem = match trimmed.get(..KEY_LENGTH).map(str::parse::<Key>) {
+                Some(Ok(_)) => Diagnostic::new(
+                    path,
+                    Severity::Error,
+                    format!(
+                        "contains {} characters after the private key",
+                        trimmed.len() - KEY_LENGTH
+                    ),
+                )
+                .with_fix(format!(
+                    "remove everything after the first {} characters of {}",
+                    KEY_LENGTH,
+                    path.display()
+                )),
+                _ => Diagnostic::new(
+                    path,
+                    Severity::Error,
+                    format!("doesn't contain a {} character hex encoded private key", KEY_LENGTH),
+                ),
+            };
+
+            return diagnosis.problems.push(problem);
+        }
+    };
+
+    let derived = match KeyPair::from_private(private_key) {
+        Ok(pair) => pair.public,
+        Err(e) => {
+            return diagnosis
+                .problems
+                .push(Diagnostic::new(path, Severity::Error, format!("{:#}", e)));
+        }
+    };
+
+    if derived != public_key {
+        let message = format!(
+            "contains the private key for {} ({}), not {} ({})",
+            derived,
+            derived.fingerprint(),
+            public_key,
+            public_key.fingerprint()
+        );
+        let problem = Diagnostic::new(path, Severity::Error, message).with_fix(format!(
+            "mv {} {}",
+            path.display(),
+            keydir.join(derived.to_string()).display()
+        ));
+        return diagnosis.problems.push(problem);
+    }
+
+    diagnosis.keys.push(public_key);
+}
+
+fn diagnose_file(path: &Path, source: &KeySource, keys: &[Key], diagnosis: &mut Diagnosis) {
+    let public_key = match SecretsFile::load(path) {
+        Ok(secrets_file) => secrets_file.public_key(),
+        Err(e) => {
+            let problem = Diagnostic::new(path, Severity::Error, format!("can't be parsed: {:#}", e));
+            return diagnosis.problems.push(problem);
+        }
+    };
+
+    let key_available = public_key.as_ref().is_some_and(|k| keys.contains(k));
+    match &public_key {
+        None => {
+            let problem = Diagnostic::new(path, Severity::Error, "has a missing or invalid _public_key")
+                .with_fix("set _public_key to the public key the file's values are encrypted with");
+            diagnosis.problems.push(problem);
+        }
+        Some(key) if !key_available => {
+            let message = format!("no usable private key for {} ({})", key, key.fingerprint());
+            let fix = match source {
+                KeySource::Keydir(keydir) => format!(
+                    "add the private key as {}, or set EJSON_KEYDIR (or --keydir) to the directory containing it",
+                    keydir.join(key.to_string()).display()
+                ),
+                KeySource::Env(var, _) => format!("set {} to the private key for {}", var, key),
+            };
+            diagnosis
+                .problems
+                .push(Diagnostic::new(path, Severity::Error, message).with_fix(fix));
+        }
+        Some(_) => {}
+    }
+
+    diagnosis.files.push(DiagnosedFile {
+        path: path.display().to_string(),
+        public_key,
+        key_available,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+    const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+
+    fn write(path: &Path, contents: &str, mode: u32) -> anyhow::Result<()> {
+        fs::write(path, contents)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        Ok(())
+    }
+
+    #[test]
+    fn diagnose_keys() -> anyhow::Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let keydir = dir.path().join("keys");
+        fs::create_dir(&keydir)?;
+        fs::set_permissions(&keydir, fs::Permissions::from_mode(0o700))?;
+
+        let other = KeyPair::generate()?;
+        write(&keydir.join(PUB_KEY), &format!("{}\n", PRIV_KEY), 0o644)?;
+        write(
+            &keydir.join(other.public_key()),
+            &format!("{}nope", other.private_key()),
+            0o600,
+        )?;
+        write(&keydir.join(KeyPair::generate()?.public_key()), PRIV_KEY, 0o600)?;
+        write(&keydir.join("README"), "nope", 0o600)?;
+
+        let secrets = dir.path().join("secrets.ejson");
+        let missing = dir.path().join("missing.ejson");
+        let invalid = dir.path().join("invalid.ejson");
+        fs::write(&secrets, json!({ "_public_key": PUB_KEY }).to_string())?;
+        fs::write(&missing, json!({ "_public_key": other.public_key() }).to_string())?;
+        fs::write(&invalid, "{}")?;
+
+        let diagnosis = diagnose(&keydir, &[&secrets, &missing, &invalid]);
+        assert!(!diagnosis.is_ok());
+        assert_eq!(vec![PUB_KEY.parse::<Key>()?], diagnosis.keys);
+        assert_eq!(
+            vec![true, false, false],
+            diagnosis.files.iter().map(|f| f.key_available).collect::<Vec<_>>()
+        );
+
+        let message = |path: &Path| {
+            diagnosis
+                .problems
+                .iter()
+                .find(|p| p.path == path.display().to_string())
+                .map(|p| (p.severity, p.message.clone(), p.fix.clone()))
+        };
+
+        assert_eq!(
+            Some((
+                Severity::Warning,
+                "is accessible to other users (mode 0644)".to_string(),
+                Some(format!("chmod 600 {}", keydir.join(PUB_KEY).display()))
+            )),
+            message(&keydir.join(PUB_KEY))
+        );
+        assert_eq!(
+            Some(Severity::Error),
+            message(&keydir.join(other.public_key())).map(|(s, _, _)| s)
+        );
+        assert!(
+            message(&keydir.join(other.public_key()))
+                .unwrap()
+                .1
+                .contains("4 characters after")
+        );
+        assert!(diagnosis.problems.iter().any(|p| {
+            p.message
+                .starts_with(&format!("contains the private key for {}", PUB_KEY))
+        }));
+        assert_eq!(
+            Some(Severity::Warning),
+            message(&keydir.join("README")).map(|(s, _, _)| s)
+        );
+        assert!(message(&missing).unwrap().1.starts_with("no usable private key"));
+        assert_eq!("has a missing or invalid _public_key", message(&invalid).unwrap().1);
+
+        let json = diagnosis.to_json();
+        assert_eq!(PUB_KEY, json["keys"][0]["public_key"]);
+        assert_eq!(true, json["files"][0]["key_available"]);
+        assert!(!json.to_string().contains(PRIV_KEY), "private keys are never included");
+
+        Ok(())
+    }
+
+    #[test]
+    fn diagnose_key_sources() -> anyhow::Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let keydir = dir.path().join("keys");
+        let other_keydir = dir.path().join("other");
+        fs::create_dir(&keydir)?;
+        fs::create_dir(&other_keydir)?;
+        write(&other_keydir.join(PUB_KEY), PRIV_KEY, 0o600)?;
+
+        let other = dir.path().join("other.ejson");
+        let env = dir.path().join("env.ejson");
+        let wrong_env = dir.path().join("wrong.ejson");
+        [&other, &env, &wrong_env]
+            .iter()
+            .try_for_each(|path| fs::write(path, json!({ "_public_key": PUB_KEY }).to_string()))?;
+
+        let diagnosis = diagnose_with(&keydir, &[&other, &env, &wrong_env], |path| {
+            match path.file_stem()?.to_str()? {
+                "other" => Some(KeySource::Keydir(other_keydir.clone())),
+                "env" => Some(KeySource::Env("KEY".into(), PRIV_KEY.parse().ok())),
+                _ => Some(KeySource::Env("KEY".into(), None)),
+            }
+        });
+
+        assert_eq!(vec![PUB_KEY.parse::<Key>()?], diagnosis.keys);
+        assert_eq!(
+            vec![true, true, false],
+            diagnosis.files.iter().map(|f| f.key_available).collect::<Vec<_>>()
+        );
+        assert_eq!(1, diagnosis.problems.len());
+        assert_eq!(
+            Some(format!("set KEY to the private key for {}", PUB_KEY)),
+            diagnosis.problems[0].fix
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn diagnose_missing_keydir() -> anyhow::Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let keydir = dir.path().join("keys");
+
+        let diagnosis = diagnose::<&Path>(&keydir, &[]);
+        assert!(!diagnosis.is_ok());
+        assert_eq!(1, diagnosis.problems.len());
+        assert!(diagnosis.problems[0].message.starts_with("the keydir can't be read"));
+        assert!(diagnosis.problems[0].fix.as_ref().unwrap().contains("mkdir -p"));
+
+        Ok(())
+    }
+}
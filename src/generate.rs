@@ -0,0 +1,193 @@
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use rand::{Rng, RngCore};
+use serde_json::{Value, json};
+
+/// The EFF's large wordlist for passphrases (https://www.eff.org/dice), licensed under CC BY 3.0 US.
+const WORDS: &str = include_str!("generate/eff_large_wordlist.txt");
+const ALNUM: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const WORD_SEPARATOR: &str = ".";
+const SEED_LENGTH: usize = 32;
+
+/// The default length of random values, see [Generator::Random].
+pub const DEFAULT_LENGTH: usize = 32;
+
+/// The default number of words in a passphrase.
+pub const DEFAULT_WORDS: usize = 6;
+
+/// The characters used for random values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Letters and digits.
+    Alnum,
+    /// Random bytes, hex encoded.
+    Hex,
+    /// Random bytes, base64 encoded.
+    Base64,
+    /// Words from the EFF's large wordlist, separated by dots.
+    Words,
+}
+
+/// Generates new secret values.
+///
+/// ```
+/// use rejson::{Charset, Generator};
+///
+/// let value = Generator::Random { charset: Charset::Hex, length: 16 }.generate()?;
+/// assert_eq!(32, value.as_str().unwrap().len());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    /// A random string. The length is the number of characters for [Charset::Alnum], bytes (before
+    /// encoding) for [Charset::Hex] and [Charset::Base64], and words for [Charset::Words].
+    Random { charset: Charset, length: usize },
+    /// A random (version 4) UUID.
+    Uuid,
+    /// An Ed25519 key pair, as an object with the base64 encoded `_public_key` (which isn't
+    /// encrypted) and `private_key` (the 32-byte seed).
+    Ed25519,
+}
+
+impl Generator {
+    /// Generates a new value.
+    pub fn generate(&self) -> Result<Value> {
+        let mut rng = rand::rng();
+        let b64 = base64::engine::general_purpose::STANDARD;
+
+        let value = match *self {
+            Self::Random { length: 0, .. } => return Err(anyhow!("length must be greater than 0")),
+            Self::Random {
+                charset: Charset::Alnum,
+                length,
+            } => (0..length)
+                .map(|_| ALNUM[rng.random_range(0..ALNUM.len())] as char)
+                .collect::<String>()
+                .into(),
+            Self::Random {
+                charset: Charset::Hex,
+                length,
+            } => hex(&random_bytes(length)).into(),
+            Self::Random {
+                charset: Charset::Base64,
+                length,
+            } => b64.encode(random_bytes(length)).into(),
+            Self::Random {
+                charset: Charset::Words,
+                length,
+            } => {
+                let words = WORDS.lines().collect::<Vec<_>>();
+                (0..length)
+                    .map(|_| words[rng.random_range(0..words.len())])
+                    .collect::<Vec<_>>()
+                    .join(WORD_SEPARATOR)
+                    .into()
+            }
+            Self::Uuid => uuid(&random_bytes(16)).into(),
+            Self::Ed25519 => {
+                let seed = random_bytes(SEED_LENGTH);
+                let keypair = nacl::sign::generate_keypair(&seed);
+                json!({ "_public_key": b64.encode(keypair.pkey), "private_key": b64.encode(seed) })
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Formats the random bytes as a version 4 (RFC 9562) UUID.
+fn uuid(bytes: &[u8]) -> String {
+    let mut bytes = bytes.to_vec();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random(charset: Charset, length: usize) -> String {
+        let value = Generator::Random { charset, length }.generate().unwrap();
+        value.as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn generate_random() {
+        let value = random(Charset::Alnum, 40);
+        assert_eq!(40, value.len());
+        assert!(value.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(value, random(Charset::Alnum, 40));
+
+        let value = random(Charset::Hex, 16);
+        assert_eq!(32, value.len());
+        assert!(value.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let value = random(Charset::Base64, 32);
+        assert_eq!(
+            32,
+            base64::engine::general_purpose::STANDARD.decode(value).unwrap().len()
+        );
+
+        let value = random(Charset::Words, 4);
+        assert_eq!(4, value.split(WORD_SEPARATOR).count());
+        assert!(WORDS.lines().all(|word| !word.contains(WORD_SEPARATOR)));
+        assert_eq!(7776, WORDS.lines().count());
+
+        assert!(
+            Generator::Random {
+                charset: Charset::Alnum,
+                length: 0
+            }
+            .generate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn generate_uuid() {
+        assert_eq!("00000000-0000-4000-8000-000000000000", uuid(&[0; 16]));
+        assert_eq!("ffffffff-ffff-4fff-bfff-ffffffffffff", uuid(&[0xff; 16]));
+
+        let value = Generator::Uuid.generate().unwrap();
+        assert_eq!(36, value.as_str().unwrap().len());
+    }
+
+    #[test]
+    fn generate_ed25519() {
+        // RFC 8032, section 7.1, test 1
+        let seed = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        let seed = (0..seed.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&seed[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            hex(&nacl::sign::generate_keypair(&seed).pkey)
+        );
+
+        let value = Generator::Ed25519.generate().unwrap();
+        let b64 = base64::engine::general_purpose::STANDARD;
+        assert_eq!(32, b64.decode(value["_public_key"].as_str().unwrap()).unwrap().len());
+        assert_eq!(32, b64.decode(value["private_key"].as_str().unwrap()).unwrap().len());
+    }
+}
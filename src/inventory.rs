@@ -0,0 +1,325 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use serde_json::{Value, json};
+
+use crate::{Key, SecretsFile, crypto::Message};
+
+const MESSAGE_PREFIX: &str = "EJ[";
+const CSV_HEADER: &str = "fingerprint,public_key,file,key,status,version,owner,description";
+
+/// A list of every secret in a set of secrets files, grouped by public key, without their values.
+///
+/// ```
+/// use rejson::{Inventory, SecretsFile};
+///
+/// let file: SecretsFile = r#"{ "_public_key": "nope", "password": "hunter2" }"#.parse()?;
+///
+/// let mut inventory = Inventory::default();
+/// inventory.add("secrets.ejson", &file);
+/// assert_eq!(
+///     "fingerprint,public_key,file,key,status,version,owner,description\n\
+///      ,,secrets.ejson,password,plaintext,,,\n",
+///     inventory.to_csv()
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Inventory {
+    entries: Vec<InventoryEntry>,
+}
+
+/// A single secret (a value that's encrypted, or should be).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryEntry {
+    pub file: String,
+    /// The (dot-delimited) key of the value.
+    pub key: String,
+    /// The file's public key, when it's valid.
+    pub public_key: Option<String>,
+    /// The [Key::fingerprint] of the file's public key, when it's valid.
+    pub fingerprint: Option<String>,
+    pub status: SecretStatus,
+    /// The message version of encrypted values.
+    pub version: Option<u8>,
+    /// From the value's `_meta` entry, if any.
+    pub owner: Option<String>,
+    /// From the value's `_meta` entry, if any.
+    pub description: Option<String>,
+}
+
+/// Whether a secret is encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretStatus {
+    Encrypted,
+    /// The value isn't encrypted.
+    Plaintext,
+    /// The value looks encrypted, but can't be parsed.
+    Malformed,
+}
+
+impl SecretStatus {
+    /// Returns the name used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Encrypted => "encrypted",
+            Self::Plaintext => "plaintext",
+            Self::Malformed => "malformed",
+        }
+    }
+}
+
+impl Inventory {
+    /// Adds the secrets in the file to the inventory. Values are never decrypted.
+    pub fn add<S: Into<String>>(&mut self, file: S, secrets_file: &SecretsFile) {
+        let file = file.into();
+        let public_key = secrets_file.public_key();
+        let metadata = secrets_file
+            .metadata()
+            .into_iter()
+            .map(|m| (m.key.clone(), m))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut secrets = vec![];
+        secrets_file.visit(false, |entry| {
+            if let Some(s) = entry.value.as_str().filter(|_| entry.is_eligible()) {
+                let (status, version) = match s.parse::<Message>() {
+                    Ok(message) => (SecretStatus::Encrypted, Some(message.version)),
+                    Err(_) if s.starts_with(MESSAGE_PREFIX) => (SecretStatus::Malformed, None),
+                    Err(_) => (SecretStatus::Plaintext, None),
+                };
+
+                secrets.push((entry.path, status, version));
+            }
+        });
+
+        self.entries.extend(secrets.into_iter().map(|(key, status, version)| {
+            let meta = metadata.get(&key).cloned().unwrap_or_default();
+
+            InventoryEntry {
+                file: file.clone(),
+                public_key: public_key.as_ref().map(Key::to_string),
+                fingerprint: public_key.as_ref().map(Key::fingerprint),
+                status,
+                version,
+                owner: meta.owner,
+                description: meta.description,
+                key,
+            }
+        }));
+    }
+
+    /// Returns the secrets grouped by the fingerprint of their file's public key (secrets in files
+    /// without a valid public key are grouped under `None`), in the order they were added within
+    /// each group.
+    pub fn groups(&self) -> BTreeMap<Option<&str>, Vec<&InventoryEntry>> {
+        self.entries.iter().fold(BTreeMap::new(), |mut groups, entry| {
+            groups
+                .entry(entry.fingerprint.as_deref())
+                .or_insert_with(Vec::new)
+                .push(entry);
+            groups
+        })
+    }
+
+    /// Returns the inventory as Markdown, with a table per public key.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+
+        self.groups().into_iter().for_each(|(fingerprint, entries)| {
+            let files = entries.iter().map(|e| e.file.as_str()).collect::<BTreeSet<_>>();
+            let title = match entries[0].public_key.as_deref() {
+                Some(public_key) => format!("Public key {} (`{}`)", fingerprint.unwrap_or_default(), public_key),
+                None => "Missing or invalid public key".into(),
+            };
+
+            let _ = writeln!(
+                out,
+                "## {}\n\n{} secret(s) in {} file(s)\n\n\
+                 | File | Key | Status | Version | Owner | Description |\n\
+                 | --- | --- | --- | --- | --- | --- |",
+                title,
+                entries.len(),
+                files.len()
+            );
+
+            entries.iter().for_each(|e| {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} | {} |",
+                    markdown_cell(&e.file),
+                    markdown_cell(&e.key),
+                    e.status.as_str(),
+                    e.version.map(|v| v.to_string()).unwrap_or_default(),
+                    markdown_cell(e.owner.as_deref().unwrap_or_default()),
+                    markdown_cell(e.description.as_deref().unwrap_or_default()),
+                );
+            });
+
+            out.push('\n');
+        });
+
+        out
+    }
+
+    /// Returns the inventory as CSV, with a row per secret grouped by public key.
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("{}\n", CSV_HEADER);
+
+        self.groups().into_values().flatten().for_each(|e| {
+            let version = e.version.map(|v| v.to_string()).unwrap_or_default();
+            let row = [
+                e.fingerprint.as_deref().unwrap_or_default(),
+                e.public_key.as_deref().unwrap_or_default(),
+                &e.file,
+                &e.key,
+                e.status.as_str(),
+                &version,
+                e.owner.as_deref().unwrap_or_default(),
+                e.description.as_deref().unwrap_or_default(),
+            ];
+
+            let _ = writeln!(out, "{}", row.map(csv_field).join(","));
+        });
+
+        out
+    }
+
+    /// Returns the inventory as JSON, with an object per public key.
+    pub fn to_json(&self) -> Value {
+        let groups = self
+            .groups()
+            .into_iter()
+            .map(|(fingerprint, entries)| {
+                let secrets = entries
+                    .iter()
+                    .map(|e| {
+                        json!({
+                            "file": e.file,
+                            "key": e.key,
+                            "status": e.status.as_str(),
+                            "version": e.version,
+                            "owner": e.owner,
+                            "description": e.description,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                json!({
+                    "fingerprint": fingerprint,
+                    "public_key": entries[0].public_key,
+                    "secrets": secrets,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({ "public_keys": groups })
+    }
+}
+
+fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+    const ENCRYPTED: &str = "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+    fn inventory() -> Inventory {
+        let file = |value: Value| SecretsFile { value };
+        let mut inventory = Inventory::default();
+
+        inventory.add(
+            "b.ejson",
+            &file(json!({
+                "_public_key": PUB_KEY,
+                "database": {
+                    "password": ENCRYPTED,
+                    "_comment": "skipped",
+                    "_meta": { "password": { "owner": "team-db", "description": "Primary, \"rw\"" } }
+                },
+                "port": 5432
+            })),
+        );
+        inventory.add("a.ejson", &file(json!({ "token": "EJ[1:nope]" })));
+        inventory.add("c.ejson", &file(json!({ "_public_key": PUB_KEY, "plain": "value" })));
+
+        inventory
+    }
+
+    #[test]
+    fn groups() {
+        let inventory = inventory();
+        let fingerprint = PUB_KEY.parse::<Key>().unwrap().fingerprint();
+        let groups = inventory.groups();
+
+        assert_eq!(
+            vec![None, Some(fingerprint.as_str())],
+            groups.keys().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(SecretStatus::Malformed, groups[&None][0].status);
+
+        let entries = &groups[&Some(fingerprint.as_str())];
+        assert_eq!(
+            vec![
+                ("b.ejson", "database.password", SecretStatus::Encrypted, Some(1)),
+                ("c.ejson", "plain", SecretStatus::Plaintext, None),
+            ],
+            entries
+                .iter()
+                .map(|e| (e.file.as_str(), e.key.as_str(), e.status, e.version))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("team-db".into()), entries[0].owner);
+    }
+
+    #[test]
+    fn formats() {
+        let inventory = inventory();
+        let fingerprint = PUB_KEY.parse::<Key>().unwrap().fingerprint();
+        let output = [
+            inventory.to_markdown(),
+            inventory.to_csv(),
+            inventory.to_json().to_string(),
+        ];
+        assert!(
+            output.iter().all(|s| !s.contains(ENCRYPTED)),
+            "values are never included"
+        );
+
+        let csv = inventory.to_csv();
+        assert_eq!(
+            vec![
+                CSV_HEADER.to_string(),
+                ",,a.ejson,token,malformed,,,".to_string(),
+                format!(
+                    "{},{},b.ejson,database.password,encrypted,1,team-db,\"Primary, \"\"rw\"\"\"",
+                    fingerprint, PUB_KEY
+                ),
+                format!("{},{},c.ejson,plain,plaintext,,,", fingerprint, PUB_KEY),
+            ],
+            csv.lines().collect::<Vec<_>>()
+        );
+
+        let markdown = inventory.to_markdown();
+        assert!(markdown.contains("## Missing or invalid public key\n\n1 secret(s) in 1 file(s)\n"));
+        assert!(markdown.contains("2 secret(s) in 2 file(s)"));
+        assert!(markdown.contains("| b.ejson | database.password | encrypted | 1 | team-db | Primary, \"rw\" |"));
+
+        let json = inventory.to_json();
+        assert_eq!(PUB_KEY, json["public_keys"][1]["public_key"]);
+        assert_eq!("team-db", json["public_keys"][1]["secrets"][0]["owner"]);
+    }
+}
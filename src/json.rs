@@ -1,18 +1,39 @@
-use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+use std::{collections::HashMap, fmt, path::Path, str::FromStr, time::SystemTime};
 
-use anyhow::Result;
-use serde_json::Value;
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use serde_json::{Map, Serializer, Value, ser::PrettyFormatter};
 
-use crate::crypto::Key;
+use crate::{
+    crypto::{Key, Message},
+    map::{safe_key, split_key},
+    meta::{META_KEY, Metadata, timestamp},
+};
 
-const PK_KEY: &str = "_public_key";
-const IGNORE_PREFIX: &str = "_";
+pub(crate) const PK_KEY: &str = "_public_key";
+pub(crate) const IGNORE_PREFIX: &str = "_";
 
 #[derive(Debug)]
 pub struct SecretsFile {
     pub(crate) value: Value,
 }
 
+/// A value found by [SecretsFile::visit].
+pub(crate) struct Entry<'a> {
+    /// The key of the value, in the dot-notation used by [crate::SecretsMap].
+    pub path: String,
+    /// The last segment of the key, as it appears in the document.
+    pub key: &'a str,
+    pub value: &'a Value,
+}
+
+impl Entry<'_> {
+    /// Returns whether the value is one that's encrypted (see [SecretsFile::transform]).
+    pub fn is_eligible(&self) -> bool {
+        self.value.is_string() && !self.key.starts_with(IGNORE_PREFIX)
+    }
+}
+
 impl SecretsFile {
     /// Creates a new [SecretsFile] by reading the specified file.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -29,7 +50,8 @@ impl SecretsFile {
     }
 
     /// Performs the supplied transformation function on each eligible value in the document.
-    /// Eligible in this case refers to string values who's key does not start with an underscore.
+    /// Eligible in this case refers to string values who's key does not start with an underscore
+    /// and that aren't in a `_meta` object (see [Metadata]).
     ///
     /// The transformer will be called for each eligible string value and is expected to return a
     /// new value to be used in it's place.
@@ -40,7 +62,180 @@ impl SecretsFile {
             .as_object_mut()
             .unwrap()
             .iter_mut()
-            .try_for_each(|(k, v)| transform(k, v, &transformer))
+            .try_for_each(|(k, v)| transform(&safe_key(k.as_str()), k, v, &transformer))
+    }
+
+    /// Performs the supplied transformation function on the eligible values at (or under) the
+    /// supplied key, in the dot-notation used by [crate::SecretsMap]. See [SecretsFile::transform].
+    pub fn transform_key<F: Fn(String) -> Result<String>>(&mut self, key: &str, transformer: F) -> Result<()> {
+        let segments = split_key(key);
+        let (last, parents) = segments.split_last().ok_or_else(|| anyhow!("key is required"))?;
+
+        let value = parents
+            .iter()
+            .try_fold(&mut self.value, |value, segment| value.get_mut(*segment))
+            .and_then(|value| value.get_mut(*last))
+            .ok_or_else(|| anyhow!("{} not found", key))?;
+
+        transform(key, last, value, &transformer)
+    }
+
+    /// Returns the value of the supplied key, in the dot-notation used by [crate::SecretsMap].
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        split_key(key)
+            .into_iter()
+            .try_fold(&self.value, |value, segment| value.get(segment))
+    }
+
+    /// Sets the value of the supplied key, in the dot-notation used by [crate::SecretsMap]. Missing
+    /// parent objects are created when `create` is set, otherwise they're an error.
+    pub fn set(&mut self, key: &str, value: Value, create: bool) -> Result<()> {
+        let segments = split_key(key);
+        let (last, parents) = segments.split_last().ok_or_else(|| anyhow!("key is required"))?;
+
+        let mut parent = &mut self.value;
+        for (i, segment) in parents.iter().enumerate() {
+            let path = || segments[..=i].join(".");
+            let object = parent
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("{} is not an object", segments[..i].join(".")))?;
+
+            parent = match object.contains_key(*segment) || create {
+                true => object.entry(*segment).or_insert_with(|| Value::Object(Map::new())),
+                false => return Err(anyhow!("{} not found (use --create to create it)", path())),
+            };
+        }
+
+        parent
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("{} is not an object", parents.join(".")))?
+            .insert(last.to_string(), value);
+
+        Ok(())
+    }
+
+    /// Removes the value of the supplied key, in the dot-notation used by [crate::SecretsMap],
+    /// returning it.
+    pub fn remove(&mut self, key: &str) -> Result<Value> {
+        let segments = split_key(key);
+        let (last, parents) = segments.split_last().ok_or_else(|| anyhow!("key is required"))?;
+
+        parents
+            .iter()
+            .try_fold(&mut self.value, |value, segment| value.get_mut(*segment))
+            .and_then(Value::as_object_mut)
+            .and_then(|object| object.shift_remove(*last))
+            .ok_or_else(|| anyhow!("{} not found", key))
+    }
+
+    /// Returns the key (in the dot-notation used by [crate::SecretsMap]) of every value under the
+    /// supplied key (or in the whole file), in the order they appear, along with whether the value
+    /// is encrypted.
+    pub fn keys(&self, key: Option<&str>) -> Result<Vec<(String, bool)>> {
+        let mut keys = vec![];
+        let mut visitor = |entry: Entry| {
+            let encrypted = entry.value.as_str().is_some_and(Message::is_valid);
+            keys.push((entry.path, encrypted));
+        };
+
+        match key {
+            Some(key) => {
+                let value = self.get(key).ok_or_else(|| anyhow!("{} not found", key))?;
+                let last = split_key(key).last().copied().unwrap_or_default();
+                visit(key.to_string(), last, value, true, &mut visitor);
+            }
+            None => self.visit(true, visitor),
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns the key (in the dot-notation used by [crate::SecretsMap]) of every eligible value
+    /// (see [SecretsFile::transform]) that isn't encrypted yet.
+    pub fn plaintext_keys(&self) -> Vec<String> {
+        let mut keys = vec![];
+        self.visit(false, |entry| {
+            if entry.is_eligible() && !entry.value.as_str().is_some_and(Message::is_valid) {
+                keys.push(entry.path);
+            }
+        });
+
+        keys
+    }
+
+    /// Calls the visitor with every value in the document that isn't a non-empty object, depth
+    /// first in the order they appear. The contents of `_meta` objects (see [Metadata]) are only
+    /// visited when `metadata` is set.
+    pub(crate) fn visit<'a, F: FnMut(Entry<'a>)>(&'a self, metadata: bool, mut visitor: F) {
+        self.value.as_object().into_iter().flatten().for_each(|(k, v)| {
+            visit(safe_key(k.as_str()), k, v, metadata, &mut visitor);
+        });
+    }
+
+    /// Returns the metadata of every value that has any, in the order the `_meta` objects appear.
+    pub fn metadata(&self) -> Vec<Metadata> {
+        let mut metadata = vec![];
+        collect_metadata(None, &self.value, &mut metadata);
+        metadata
+    }
+
+    /// Records that the value of the supplied key was rotated at the given time, returning whether
+    /// it was recorded. Only values that have a `_meta` entry are tracked, so files that don't use
+    /// metadata are left as they are.
+    pub fn set_rotated_at(&mut self, key: &str, at: SystemTime) -> Result<bool> {
+        let (parent, last) = self.parent_mut(key)?;
+        let Some(entry) = parent
+            .get_mut(META_KEY)
+            .and_then(|meta| meta.get_mut(last))
+            .and_then(Value::as_object_mut)
+        else {
+            return Ok(false);
+        };
+
+        entry.insert("rotated_at".into(), Value::String(timestamp(at)?));
+        Ok(true)
+    }
+
+    /// Removes the `_meta` entry of the supplied key (and the `_meta` object when it's left empty),
+    /// returning it.
+    pub fn remove_metadata(&mut self, key: &str) -> Option<Value> {
+        let (parent, last) = self.parent_mut(key).ok()?;
+        let meta = parent.get_mut(META_KEY)?.as_object_mut()?;
+        let entry = meta.shift_remove(last);
+
+        if meta.is_empty() {
+            parent.shift_remove(META_KEY);
+        }
+
+        entry
+    }
+
+    /// Sets the `_meta` entry of the supplied key, whose parent object must exist.
+    pub fn insert_metadata(&mut self, key: &str, entry: Value) -> Result<()> {
+        let (parent, last) = self.parent_mut(key)?;
+        parent
+            .entry(META_KEY)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("{} is not an object", META_KEY))?
+            .insert(last.to_string(), entry);
+
+        Ok(())
+    }
+
+    /// Returns the object holding the value of the supplied key, along with the last segment of
+    /// the key.
+    fn parent_mut<'k>(&mut self, key: &'k str) -> Result<(&mut Map<String, Value>, &'k str)> {
+        let segments = split_key(key);
+        let (last, parents) = segments.split_last().ok_or_else(|| anyhow!("key is required"))?;
+
+        let parent = parents
+            .iter()
+            .try_fold(&mut self.value, |value, segment| value.get_mut(*segment))
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| anyhow!("{} not found", parents.join(".")))?;
+
+        Ok((parent, last))
     }
 
     /// Returns a map of all direct children of the supplied key with scalar values.
@@ -60,6 +255,37 @@ impl SecretsFile {
         })
     }
 
+    /// Returns the JSON representation using the same indentation (and trailing new line) as the
+    /// supplied document, which is typically the original contents of the file.
+    pub fn to_string_like(&self, existing: &str) -> Result<String> {
+        let indent = existing.lines().skip(1).find_map(|line| {
+            let trimmed = line.trim_start();
+            (!trimmed.is_empty() && trimmed.len() < line.len()).then(|| &line[..line.len() - trimmed.len()])
+        });
+
+        let mut json = match indent {
+            Some(indent) => {
+                let mut buffer = vec![];
+                let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+                self.value
+                    .serialize(&mut Serializer::with_formatter(&mut buffer, formatter))?;
+                String::from_utf8(buffer)?
+            }
+            None => serde_json::to_string(&self.value)?,
+        };
+
+        if existing.ends_with('\n') {
+            json.push('\n');
+        }
+
+        Ok(json)
+    }
+
+    /// Returns the file's contents as a JSON value.
+    pub fn to_json(&self) -> Value {
+        self.value.clone()
+    }
+
     /// Returns a new [SecretsFile] that is a clone of this one without the _public_key field.
     pub fn without_public_key(&self) -> Self {
         let mut value = self.value.clone();
@@ -88,21 +314,49 @@ impl FromStr for SecretsFile {
     }
 }
 
-fn transform<F: Fn(String) -> Result<String>>(key: &str, value: &mut Value, tfn: &F) -> Result<()> {
+/// Visits the value (recursively). See [SecretsFile::visit].
+fn visit<'a, F: FnMut(Entry<'a>)>(path: String, key: &'a str, value: &'a Value, metadata: bool, visitor: &mut F) {
+    match value {
+        Value::Object(_) if key == META_KEY && !metadata => {}
+        Value::Object(obj) if !obj.is_empty() => obj.iter().for_each(|(k, v)| {
+            visit(format!("{}.{}", path, safe_key(k.as_str())), k, v, metadata, visitor);
+        }),
+        _ => visitor(Entry { path, key, value }),
+    }
+}
+
+fn collect_metadata(path: Option<&str>, value: &Value, metadata: &mut Vec<Metadata>) {
+    let Some(obj) = value.as_object() else { return };
+    let child = |key: &str| match path {
+        Some(path) => format!("{}.{}", path, safe_key(key)),
+        None => safe_key(key),
+    };
+
+    obj.iter().for_each(|(k, v)| match (k.as_str(), v) {
+        (META_KEY, Value::Object(meta)) => meta
+            .iter()
+            .for_each(|(k, v)| metadata.push(Metadata::from_value(child(k), v))),
+        _ => collect_metadata(Some(&child(k)), v, metadata),
+    });
+}
+
+/// Transforms the value (recursively), adding the (dot-delimited) path of the value to any errors.
+fn transform<F: Fn(String) -> Result<String>>(path: &str, key: &str, value: &mut Value, tfn: &F) -> Result<()> {
     match value {
         Value::String(v) => {
             // Only interested in string values who's keys do not start with an underscore as
             // outlined in the EJSON spec.
             if !key.starts_with(IGNORE_PREFIX) {
-                *value = Value::String(tfn(v.to_string())?);
+                *value = Value::String(tfn(v.to_string()).with_context(|| format!("processing {}", path))?);
             }
             Ok(())
         }
+        Value::Object(_) if key == META_KEY => Ok(()),
         Value::Object(_) => value
             .as_object_mut()
             .unwrap()
             .iter_mut()
-            .try_for_each(|(k, v)| transform(k, v, tfn)),
+            .try_for_each(|(k, v)| transform(&format!("{}.{}", path, safe_key(k.as_str())), k, v, tfn)),
         _ => Ok(()),
     }
 }
@@ -180,6 +434,161 @@ mod test {
         assert_eq!(exp.to_string(), file.value.to_string());
     }
 
+    #[test]
+    fn to_string_like() -> Result<()> {
+        let file = SecretsFile {
+            value: json!({ "a": { "b": "c" } }),
+        };
+
+        assert_eq!(r#"{"a":{"b":"c"}}"#, file.to_string_like(r#"{"x":"y"}"#)?);
+        assert_eq!(
+            "{\n    \"a\": {\n        \"b\": \"c\"\n    }\n}\n",
+            file.to_string_like("{\n    \"x\": \"y\"\n}\n")?
+        );
+        assert_eq!(
+            "{\n\t\"a\": {\n\t\t\"b\": \"c\"\n\t}\n}",
+            file.to_string_like("{\n\t\"x\": \"y\"\n}")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn transform_error_path() {
+        let mut file = SecretsFile {
+            value: json!({ "a": { "file.ext": "value" } }),
+        };
+
+        let err = file.transform(|_| Err(anyhow::anyhow!("nope"))).unwrap_err();
+        assert_eq!("processing a.[file.ext]: nope", format!("{:#}", err));
+    }
+
+    #[test]
+    fn get_and_set() -> Result<()> {
+        let mut file = SecretsFile {
+            value: json!({ "a": { "file.ext": "value" }, "b": "c" }),
+        };
+
+        assert_eq!(Some(&json!("value")), file.get("a.[file.ext]"));
+        assert_eq!(None, file.get("a.missing"));
+
+        file.set("a.[file.ext]", json!("changed"), false)?;
+        file.set("a.new", json!({ "x": 1 }), false)?;
+        assert_eq!(json!({ "file.ext": "changed", "new": { "x": 1 } }), file.value["a"]);
+
+        assert!(file.set("missing.key", json!("v"), false).is_err());
+        assert!(file.set("b.key", json!("v"), true).is_err());
+
+        file.set("missing.[x.y].key", json!("v"), true)?;
+        assert_eq!(Some(&json!("v")), file.get("missing.[x.y].key"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_and_keys() -> Result<()> {
+        let encrypted = "EJ[1:AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=:AgICAgICAgICAgICAgICAgICAgICAgIC:AwMD]";
+        let mut file = SecretsFile {
+            value: json!({ "_public_key": "pk", "a": { "file.ext": encrypted, "b": "plain" }, "c": 1, "d": {} }),
+        };
+
+        assert_eq!(
+            vec![
+                ("_public_key".into(), false),
+                ("a.[file.ext]".into(), true),
+                ("a.b".into(), false),
+                ("c".into(), false),
+                ("d".into(), false),
+            ],
+            file.keys(None)?
+        );
+        assert_eq!(vec![("a.b".to_string(), false)], file.keys(Some("a.b"))?);
+
+        assert_eq!(json!(encrypted), file.remove("a.[file.ext]")?);
+        assert_eq!(json!({ "b": "plain" }), file.value["a"]);
+        assert!(file.remove("a.[file.ext]").is_err());
+        assert!(file.keys(Some("missing")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn transform_key() -> Result<()> {
+        let mut file = SecretsFile {
+            value: json!({ "a": { "b": "1", "_c": "2" }, "d": "3" }),
+        };
+
+        file.transform_key("a", |s| Ok(format!("x{}", s)))?;
+        assert_eq!(json!({ "a": { "b": "x1", "_c": "2" }, "d": "3" }), file.value);
+        assert!(file.transform_key("missing", Ok).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn metadata() -> Result<()> {
+        let mut file = SecretsFile {
+            value: json!({
+                "a": "1",
+                "nested": {
+                    "file.ext": "2",
+                    "_meta": { "file.ext": { "owner": "team", "expires_at": "2024-01-01" } }
+                }
+            }),
+        };
+
+        assert_eq!(vec!["a", "nested.[file.ext]"], file.plaintext_keys());
+
+        let visited = |metadata: bool| {
+            let mut paths = vec![];
+            file.visit(metadata, |entry| paths.push(entry.path));
+            paths
+        };
+        assert_eq!(vec!["a", "nested.[file.ext]"], visited(false));
+        assert_eq!(
+            vec![
+                "a",
+                "nested.[file.ext]",
+                "nested._meta.[file.ext].owner",
+                "nested._meta.[file.ext].expires_at"
+            ],
+            visited(true)
+        );
+
+        // metadata is never transformed
+        file.transform(|s| Ok(format!("x{}", s)))?;
+        assert_eq!("x2", file.value["nested"]["file.ext"]);
+        assert_eq!("team", file.value["nested"]["_meta"]["file.ext"]["owner"]);
+
+        let at = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(86400);
+        assert!(!file.set_rotated_at("a", at)?, "only values with metadata are tracked");
+        assert!(file.set_rotated_at("nested.[file.ext]", at)?);
+        assert!(file.set_rotated_at("missing.key", at).is_err());
+        file.insert_metadata("a", json!({}))?;
+        assert!(file.set_rotated_at("a", at)?);
+
+        let metadata = file.metadata();
+        assert_eq!(2, metadata.len());
+        assert_eq!("nested.[file.ext]", metadata[0].key);
+        assert_eq!(Some("team".into()), metadata[0].owner);
+        assert_eq!(Some("1970-01-02T00:00:00Z".into()), metadata[0].rotated_at);
+        assert_eq!("a", metadata[1].key);
+
+        let entry = file.remove_metadata("a").unwrap();
+        assert!(file.value.get("_meta").is_none());
+        file.insert_metadata("nested.b", entry)?;
+        assert_eq!(
+            vec!["file.ext", "b"],
+            file.value["nested"]["_meta"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn children() {
         let data = json!({
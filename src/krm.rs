@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+
+const RESOURCE_LIST_KIND: &str = "ResourceList";
+const RESOURCE_LIST_API_VERSION: &str = "config.kubernetes.io/v1";
+
+/// A KRM function `ResourceList`, as exchanged with kustomize and kpt on stdin/stdout.
+///
+/// ```yaml
+/// apiVersion: config.kubernetes.io/v1
+/// kind: ResourceList
+/// items: []
+/// functionConfig:
+///   apiVersion: rejson.pseudomuto.com/v1
+///   kind: SecretsGenerator
+///   metadata:
+///     name: secrets
+///   spec:
+///     files:
+///       - secrets.ejson
+///     keydir: /opt/ejson/keys
+/// ```
+///
+/// See [KrmConfig] for the supported `spec` fields.
+#[derive(Debug)]
+pub struct ResourceList {
+    value: Value,
+}
+
+/// The settings read from the `spec` of a `ResourceList`'s `functionConfig`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct KrmConfig {
+    /// The EJSON file(s) to generate secrets from (`files`).
+    pub files: Vec<String>,
+    /// The directory to load private keys from (`keydir`).
+    pub keydir: Option<String>,
+    /// The name of an environment variable holding the private key (`keyEnv`).
+    pub key_env: Option<String>,
+    /// Overrides the namespace of every secret (`namespace`).
+    pub namespace: Option<String>,
+    /// Labels added to every secret (`labels`).
+    pub labels: BTreeMap<String, String>,
+    /// Annotations added to every secret (`annotations`).
+    pub annotations: BTreeMap<String, String>,
+    /// Write values under stringData rather than data (`stringData`).
+    pub string_data: bool,
+}
+
+impl ResourceList {
+    /// Returns the function config from the `spec` of the `functionConfig`.
+    pub fn config(&self) -> Result<KrmConfig> {
+        let spec = &self.value["functionConfig"]["spec"];
+        if !spec.is_object() {
+            return Err(anyhow!("functionConfig.spec is required"));
+        }
+
+        let files = match &spec["files"] {
+            Value::String(file) => vec![file.to_string()],
+            Value::Array(files) => files
+                .iter()
+                .map(|f| f.as_str().map(String::from))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| anyhow!("functionConfig.spec.files must be a list of strings"))?,
+            _ => return Err(anyhow!("functionConfig.spec.files is required")),
+        };
+
+        Ok(KrmConfig {
+            files,
+            keydir: string(spec, "keydir")?,
+            key_env: string(spec, "keyEnv")?,
+            namespace: string(spec, "namespace")?,
+            labels: string_map(spec, "labels")?,
+            annotations: string_map(spec, "annotations")?,
+            string_data: spec["stringData"].as_bool().unwrap_or_default(),
+        })
+    }
+
+    /// Appends the supplied resources to the list's items.
+    pub fn append_items<I: IntoIterator<Item = Value>>(&mut self, items: I) {
+        if !self.value["items"].is_array() {
+            self.value["items"] = json!([]);
+        }
+
+        self.value["items"].as_array_mut().unwrap().extend(items);
+    }
+
+    /// Records an error in the list's results, optionally associated with a file.
+    pub fn add_error<S: Into<String>>(&mut self, message: S, file: Option<&str>) {
+        let mut result = json!({ "message": message.into(), "severity": "error" });
+        if let Some(path) = file {
+            result["file"] = json!({ "path": path });
+        }
+
+        if !self.value["results"].is_array() {
+            self.value["results"] = json!([]);
+        }
+
+        self.value["results"].as_array_mut().unwrap().push(result);
+    }
+
+    /// Returns the list as JSON.
+    pub fn to_json(&self) -> Value {
+        self.value.clone()
+    }
+
+    /// Returns whether any errors were recorded in the results.
+    pub fn has_errors(&self) -> bool {
+        self.value["results"]
+            .as_array()
+            .is_some_and(|results| results.iter().any(|r| r["severity"] == "error"))
+    }
+}
+
+impl Default for ResourceList {
+    fn default() -> Self {
+        Self {
+            value: json!({
+                "apiVersion": RESOURCE_LIST_API_VERSION,
+                "kind": RESOURCE_LIST_KIND,
+                "items": [],
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ResourceList {
+    /// Returns the YAML representation.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_yaml_ng::to_string(&self.value).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl FromStr for ResourceList {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: Value = serde_yaml_ng::from_str(s)?;
+        if value["kind"] != RESOURCE_LIST_KIND {
+            return Err(anyhow!("expected a {}", RESOURCE_LIST_KIND));
+        }
+
+        Ok(Self { value })
+    }
+}
+
+fn string(spec: &Value, key: &str) -> Result<Option<String>> {
+    match &spec[key] {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.to_string())),
+        _ => Err(anyhow!("functionConfig.spec.{} must be a string", key)),
+    }
+}
+
+fn string_map(spec: &Value, key: &str) -> Result<BTreeMap<String, String>> {
+    match &spec[key] {
+        Value::Null => Ok(BTreeMap::new()),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| v.as_str().map(|v| (k.to_string(), v.to_string())))
+            .collect::<Option<_>>()
+            .ok_or_else(|| anyhow!("functionConfig.spec.{} values must be strings", key)),
+        _ => Err(anyhow!("functionConfig.spec.{} must be a map", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"
+apiVersion: config.kubernetes.io/v1
+kind: ResourceList
+items:
+  - apiVersion: v1
+    kind: ConfigMap
+    metadata:
+      name: existing
+functionConfig:
+  apiVersion: rejson.pseudomuto.com/v1
+  kind: SecretsGenerator
+  metadata:
+    name: secrets
+  spec:
+    files:
+      - secrets.ejson
+    keydir: /keys
+    labels:
+      team: platform
+    stringData: true
+"#;
+
+    #[test]
+    fn config() -> Result<()> {
+        let list: ResourceList = INPUT.parse()?;
+
+        let exp = KrmConfig {
+            files: vec!["secrets.ejson".into()],
+            keydir: Some("/keys".into()),
+            labels: BTreeMap::from([("team".into(), "platform".into())]),
+            string_data: true,
+            ..Default::default()
+        };
+
+        assert_eq!(exp, list.config()?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!("kind: Nope".parse::<ResourceList>().is_err());
+        assert!(ResourceList::default().config().is_err());
+
+        let list: ResourceList = "kind: ResourceList\nfunctionConfig:\n  spec:\n    files: 1"
+            .parse()
+            .unwrap();
+        assert!(list.config().is_err());
+    }
+
+    #[test]
+    fn append_items_and_errors() -> Result<()> {
+        let mut list: ResourceList = INPUT.parse()?;
+        list.append_items([json!({ "kind": "Secret" })]);
+        assert!(!list.has_errors());
+
+        list.add_error("oops", Some("secrets.ejson"));
+        assert!(list.has_errors());
+
+        let yaml = list.to_string();
+        let value: Value = serde_yaml_ng::from_str(&yaml)?;
+        assert_eq!(2, value["items"].as_array().unwrap().len());
+        assert_eq!("Secret", value["items"][1]["kind"]);
+        assert_eq!(
+            json!([{ "message": "oops", "severity": "error", "file": { "path": "secrets.ejson" } }]),
+            value["results"]
+        );
+
+        Ok(())
+    }
+}
@@ -1,72 +1,505 @@
 use core::fmt;
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
 
+use anyhow::{Result, anyhow};
 use base64::Engine;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{Map, Value, json};
+
+use crate::{BASE64_PREFIX, map::split_first_segment, meta::META_KEY};
+
+mod diff;
+mod dir;
+mod types;
+
+pub use diff::{Change, KeyDiff, ManifestDiff, SecretDiff};
+pub use dir::{DirSummary, SOURCE_ANNOTATION};
 
 const KEY_DELIMITER: &str = ".";
+const METADATA_PREFIX: &str = "_";
 const NAMESPACE_KEY: &str = "_namespace";
+const NAMESPACES_KEY: &str = "_namespaces";
 const TYPE_KEY: &str = "_type";
+const IMMUTABLE_KEY: &str = "_immutable";
+const LABELS_PREFIX: &str = "_labels.";
+const ANNOTATIONS_PREFIX: &str = "_annotations.";
+const META_PREFIX: &str = "_meta.";
+const MAX_NAME_LENGTH: usize = 253;
+const MAX_NAMESPACE_LENGTH: usize = 63;
+
+lazy_static! {
+    /// A DNS-1123 subdomain, which is what Kubernetes requires for secret names.
+    static ref NAME_PATTERN: Regex =
+        Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$").unwrap();
+
+    /// A DNS-1123 label, which is what Kubernetes requires for namespaces.
+    static ref NAMESPACE_PATTERN: Regex = Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?$").unwrap();
+
+    /// The characters allowed in the keys of a secret's data.
+    static ref DATA_KEY_PATTERN: Regex = Regex::new(r"^[-._a-zA-Z0-9]+$").unwrap();
+}
 
 /// A wrapper around a map of dot-delimited keys and values that can be converted into a Kubernetes
 /// manifest of secrets.
+///
+/// Secrets are defined as `<secret>.<key>`. To define the same secret in several namespaces, set
+/// `_namespaces` to `true` and define them as `<namespace>.<secret>.<key>` instead (secrets without
+/// a namespace can still be defined as `<secret>.<key>`). Besides the data keys, each secret may
+/// define the following metadata:
+///
+/// - `_namespace`: the namespace to create the secret in.
+/// - `_type`: the secret type (e.g. `kubernetes.io/tls`).
+/// - `_labels`: an object of labels to add to the secret.
+/// - `_annotations`: an object of annotations to add to the secret.
+/// - `_immutable`: when `true`, marks the secret as immutable.
+///
+/// Secrets with a well-known `_type` (docker registry, TLS, basic-auth and ssh-auth) have their
+/// required fields validated, and docker registry secrets get their `.dockerconfigjson` generated
+/// from `server`, `username` and `password`.
+///
+/// Values prefixed with `base64:` are treated as already encoded (typically binary) data and are
+/// written to `data` verbatim.
 pub struct SecretsManifest<'a> {
-    inner: BTreeMap<&'a str, BTreeMap<&'a str, &'a str>>,
+    /// Secret definitions keyed by their (escaped) path in the source map.
+    inner: BTreeMap<&'a str, Secret<'a>>,
+    root: Option<&'a str>,
+    labels: BTreeMap<&'a str, &'a str>,
+    annotations: BTreeMap<&'a str, &'a str>,
+    namespace: Option<&'a str>,
+    namespaces: BTreeSet<&'a str>,
+    string_data: bool,
+}
+
+/// Identifies a generated secret by name and (optional) namespace.
+type SecretId<'a> = (&'a str, Option<&'a str>);
+
+/// A single secret definition parsed from the flattened keys.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Secret<'a> {
+    name: &'a str,
+    namespace: Option<&'a str>,
+    /// A `_namespace` value that disagrees with the namespace given by the layout.
+    conflicting_namespace: Option<&'a str>,
+    kind: Option<&'a str>,
+    immutable: bool,
+    labels: BTreeMap<&'a str, &'a str>,
+    annotations: BTreeMap<&'a str, &'a str>,
+    data: BTreeMap<&'a str, &'a str>,
 }
 
 impl<'a> SecretsManifest<'a> {
-    pub fn new(from: HashMap<&'a str, &'a str>) -> Self {
-        // Convert the delimited keys and values into a HashMap of secret name => <Key, Value>.
+    pub fn new(mut from: HashMap<&'a str, &'a str>) -> Self {
+        // The namespace layout is opt-in, since `<secret>.<object>.<key>` would be ambiguous.
+        let namespaced = from.remove(NAMESPACES_KEY).is_some_and(|v| v == "true");
+
+        // Convert the delimited keys and values into a map of secret path => Secret.
         //
         // NB: Converts to BTreeMap after filtering so values are sorted by key.
-        let resources =
-            from.into_iter()
-                .collect::<BTreeMap<&str, &str>>()
-                .iter()
-                .fold(BTreeMap::new(), |mut map, (k, v)| {
-                    // secret.key => name = secret, key = key
-                    // secret.[file.ext] => name = secret, key = file.ext
-                    let name = &k[..k.find(KEY_DELIMITER).unwrap()];
-                    let key = &k[k.find(KEY_DELIMITER).unwrap() + 1..];
-
-                    // Update values (creating if necessary).
-                    let values: &mut BTreeMap<&str, &str> = map.entry(name).or_default();
-                    values.insert(key.trim_matches(&['[', ']'] as &[_]), v);
+        let resources = from
+            .into_iter()
+            .filter(|(k, _)| !is_secret_meta(k))
+            .collect::<BTreeMap<&str, &str>>()
+            .into_iter()
+            .fold(BTreeMap::new(), |mut map, (k, v)| {
+                // secret.key => name = secret, key = key
+                // secret.[file.ext] => name = secret, key = file.ext
+                // namespace.secret.key => namespace = namespace, name = secret, key = key
+                let (path, namespace, name, key) = parse_key(k, namespaced);
 
-                    map
+                // Update values (creating if necessary).
+                let secret: &mut Secret = map.entry(path).or_insert_with(|| Secret {
+                    name,
+                    namespace,
+                    ..Default::default()
                 });
+                secret.insert(key, v);
 
-        Self { inner: resources }
+                map
+            });
+
+        Self {
+            inner: resources,
+            root: None,
+            labels: BTreeMap::new(),
+            annotations: BTreeMap::new(),
+            namespace: None,
+            namespaces: BTreeSet::new(),
+            string_data: false,
+        }
+    }
+
+    /// Adds the supplied labels to every secret in the manifest. Labels defined on the secret
+    /// itself take precedence.
+    pub fn with_labels<I: IntoIterator<Item = (&'a str, &'a str)>>(mut self, labels: I) -> Self {
+        self.labels.extend(labels);
+        self
+    }
+
+    /// Adds the supplied annotations to every secret in the manifest. Annotations defined on the
+    /// secret itself take precedence.
+    pub fn with_annotations<I: IntoIterator<Item = (&'a str, &'a str)>>(mut self, annotations: I) -> Self {
+        self.annotations.extend(annotations);
+        self
+    }
+
+    /// Overrides the namespace of every secret in the manifest.
+    pub fn with_namespace(mut self, namespace: &'a str) -> Self {
+        self.namespace = Some(namespace);
+        self
+    }
+
+    /// Only includes secrets defined in one of the supplied namespaces. The filter is applied to
+    /// the namespaces from the source, before any override from [SecretsManifest::with_namespace].
+    pub fn with_namespace_filter<I: IntoIterator<Item = &'a str>>(mut self, namespaces: I) -> Self {
+        self.namespaces.extend(namespaces);
+        self
+    }
+
+    /// Sets the key the secrets were nested under in the source file. This is used to report the
+    /// full path of invalid names and keys.
+    pub fn with_root(mut self, root: &'a str) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    /// When enabled, values are written as plain text under `stringData` rather than base64
+    /// encoded under `data`.
+    pub fn with_string_data(mut self, enabled: bool) -> Self {
+        self.string_data = enabled;
+        self
     }
 }
 
-impl fmt::Display for SecretsManifest<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl SecretsManifest<'_> {
+    /// Ensures every secret name is a valid DNS-1123 subdomain, every namespace is a valid DNS-1123
+    /// label, every data key only contains characters allowed by Kubernetes, and that no two
+    /// definitions resolve to the same secret.
+    /// All problems are reported at once, each with the path of the offending value in the source
+    /// file.
+    pub fn validate(&self) -> Result<()> {
         let b64 = base64::engine::general_purpose::STANDARD;
+        let (_, mut errors) = self.resolve();
+
+        if let Some(ns) = self.namespace.filter(|ns| !is_dns_label(ns)) {
+            errors.push(format!("namespace {} must be a valid DNS-1123 label", ns));
+        }
+
+        self.inner.iter().for_each(|(path, secret)| {
+            let path = self.path(path);
+
+            if let Some(ns) = secret.conflicting_namespace {
+                errors.push(format!(
+                    "{}.{}: namespace {} conflicts with namespace {} from the layout",
+                    path,
+                    NAMESPACE_KEY,
+                    ns,
+                    secret.namespace.unwrap_or_default()
+                ));
+            }
 
-        self.inner.iter().try_for_each(|(k, data)| {
-            writeln!(f, "---")?;
-            writeln!(f, "apiVersion: v1")?;
-            writeln!(f, "kind: Secret")?;
-            writeln!(f, "metadata:")?;
-            writeln!(f, "  name: {}", k)?;
+            // Namespaces become directories with --out-dir, so they're checked even when overridden.
+            if let Some(ns) = secret.namespace.filter(|ns| !is_dns_label(ns)) {
+                errors.push(format!("{}: namespace {} must be a valid DNS-1123 label", path, ns));
+            }
 
-            if let Some(ns) = data.get(NAMESPACE_KEY) {
-                writeln!(f, "  namespace: {}", ns)?;
+            if !is_dns_subdomain(secret.name) {
+                errors.push(format!("{}: secret name must be a valid DNS-1123 subdomain", path));
             }
 
-            if let Some(kind) = data.get(TYPE_KEY) {
-                writeln!(f, "type: {}", kind)?;
+            secret.data.keys().filter(|key| !is_data_key(key)).for_each(|key| {
+                errors.push(format!(
+                    "{}.{}: key must consist of alphanumeric characters, '-', '_' or '.'",
+                    path,
+                    wrap_key(key)
+                ))
+            });
+
+            secret
+                .data
+                .iter()
+                .filter(|(_, v)| v.strip_prefix(BASE64_PREFIX).is_some_and(|v| b64.decode(v).is_err()))
+                .for_each(|(key, _)| {
+                    errors.push(format!(
+                        "{}.{}: value has the {} prefix but is not valid base64",
+                        path,
+                        wrap_key(key),
+                        BASE64_PREFIX
+                    ))
+                });
+
+            if let Err(type_errors) = types::data(secret.kind, &secret.data) {
+                errors.extend(type_errors.into_iter().map(|(key, message)| match key {
+                    Some(key) => format!("{}.{}: {}", path, wrap_key(key), message),
+                    None => format!("{}: {}", path, message),
+                }));
             }
+        });
 
-            writeln!(f, "data:")?;
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("invalid kubernetes secrets:\n  {}", errors.join("\n  "))),
+        }
+    }
+
+    /// Returns the Kubernetes Secret resources, sorted by name and namespace.
+    pub fn resources(&self) -> Vec<Value> {
+        self.resolve()
+            .0
+            .into_iter()
+            .map(|((_, namespace), secret)| self.resource(namespace, secret))
+            .collect()
+    }
+
+    /// Writes each secret to its own file under `dir`, named `<namespace>/<name>.yaml` (or
+    /// `<name>.yaml` for secrets without a namespace). The secrets are validated first (see
+    /// [SecretsManifest::validate]), so nothing is written outside of `dir`.
+    ///
+    /// Every secret is annotated with the path of the source file relative to `dir` (see
+    /// [SOURCE_ANNOTATION]) so that files generated from the same source on an earlier run, whose
+    /// secrets no longer exist, can be removed. Files that are already up to date are not rewritten.
+    /// When `kustomization` is set, a `kustomization.yaml` listing every generated file in the
+    /// directory is written as well.
+    pub fn write_dir<P: AsRef<Path>, S: AsRef<Path>>(
+        &self,
+        dir: P,
+        source: S,
+        kustomization: bool,
+    ) -> Result<DirSummary> {
+        self.validate()?;
+        dir::write(dir.as_ref(), source.as_ref(), self.resources(), kustomization)
+    }
+
+    /// Compares the secrets in the existing manifest (a YAML file, or a directory of them) to the
+    /// ones this manifest would generate, reporting added, removed and changed keys per secret.
+    /// Only Secrets are compared; other resources in the existing manifests are ignored.
+    pub fn diff<P: AsRef<Path>>(&self, existing: P) -> Result<ManifestDiff> {
+        Ok(diff::compare(diff::read(existing.as_ref())?, self.resources()))
+    }
+
+    /// Returns the secrets to generate keyed (and sorted) by name and namespace, after applying the
+    /// namespace filter and override. Definitions that collide with an earlier one are skipped and
+    /// reported in the returned errors.
+    fn resolve(&self) -> (BTreeMap<SecretId<'_>, &Secret<'_>>, Vec<String>) {
+        let mut errors = vec![];
+        let mut paths: BTreeMap<_, &str> = BTreeMap::new();
+
+        let secrets = self
+            .inner
+            .iter()
+            .filter(|(_, secret)| {
+                self.namespaces.is_empty() || secret.namespace.is_some_and(|ns| self.namespaces.contains(ns))
+            })
+            .fold(BTreeMap::new(), |mut map, (path, secret)| {
+                let id = (secret.name, self.namespace.or(secret.namespace));
 
-            data.iter()
-                .filter(|&(k, _)| k != &NAMESPACE_KEY && k != &TYPE_KEY)
-                .try_for_each(|(k, v)| writeln!(f, "  {}: {}", k, b64.encode(v)))
+                match paths.get(&id) {
+                    Some(existing) => errors.push(format!(
+                        "{}: secret {} is already defined at {}",
+                        self.path(path),
+                        display_id(id),
+                        self.path(existing)
+                    )),
+                    None => {
+                        paths.insert(id, *path);
+                        map.insert(id, secret);
+                    }
+                }
+
+                map
+            });
+
+        (secrets, errors)
+    }
+
+    /// Returns the full path of the secret in the source file.
+    fn path(&self, path: &str) -> String {
+        match self.root {
+            Some(root) => format!("{}.{}", root, path),
+            None => path.to_string(),
+        }
+    }
+
+    /// Builds the Kubernetes resource for the named secret.
+    fn resource(&self, namespace: Option<&str>, secret: &Secret) -> Value {
+        let b64 = base64::engine::general_purpose::STANDARD;
+
+        let mut metadata = json!({ "name": secret.name });
+        if let Some(ns) = namespace {
+            metadata["namespace"] = ns.into();
+        }
+        let labels = merge(&self.labels, &secret.labels);
+        if let Some(labels) = object(labels.iter().map(|(k, v)| (k.to_string(), *v))) {
+            metadata["labels"] = labels;
+        }
+
+        let annotations = merge(&self.annotations, &secret.annotations);
+        if let Some(annotations) = object(annotations.iter().map(|(k, v)| (k.to_string(), *v))) {
+            metadata["annotations"] = annotations;
+        }
+
+        let mut resource = json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": metadata,
+        });
+
+        if let Some(kind) = secret.kind {
+            resource["type"] = kind.into();
+        }
+
+        if secret.immutable {
+            resource["immutable"] = true.into();
+        }
+
+        // Values with the base64 prefix are already encoded (and possibly binary), so they always go
+        // into data verbatim, even when writing everything else as stringData.
+        let mut data = Map::new();
+        let mut string_data = Map::new();
+
+        // Invalid secrets are rejected by validate, so fall back to the data as is.
+        let values = types::data(secret.kind, &secret.data)
+            .unwrap_or_else(|_| secret.data.iter().map(|(k, v)| (*k, Cow::Borrowed(*v))).collect());
+
+        values.iter().for_each(|(k, v)| {
+            match v.strip_prefix(BASE64_PREFIX) {
+                Some(encoded) => data.insert(k.to_string(), encoded.into()),
+                None if self.string_data => string_data.insert(k.to_string(), v.as_ref().into()),
+                None => data.insert(k.to_string(), b64.encode(v.as_bytes()).into()),
+            };
+        });
+
+        if !string_data.is_empty() {
+            resource["stringData"] = string_data.into();
+        }
+
+        if !data.is_empty() || !self.string_data {
+            resource["data"] = data.into();
+        }
+
+        resource
+    }
+}
+
+impl<'a> Secret<'a> {
+    /// Inserts the (secret relative) key and value, routing metadata keys to the right place.
+    fn insert(&mut self, key: &'a str, value: &'a str) {
+        match key {
+            NAMESPACE_KEY => match self.namespace {
+                Some(ns) if ns != value => self.conflicting_namespace = Some(value),
+                _ => self.namespace = Some(value),
+            },
+            TYPE_KEY => self.kind = Some(value),
+            IMMUTABLE_KEY => self.immutable = value == "true",
+            _ => {
+                if let Some(label) = key.strip_prefix(LABELS_PREFIX) {
+                    self.labels.insert(unwrap_key(label), value);
+                } else if let Some(annotation) = key.strip_prefix(ANNOTATIONS_PREFIX) {
+                    self.annotations.insert(unwrap_key(annotation), value);
+                } else if !key.starts_with(META_PREFIX) {
+                    // The metadata of the values (see [crate::Metadata]) isn't part of the secret.
+                    self.data.insert(unwrap_key(key), value);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for SecretsManifest<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.resources().iter().try_for_each(|resource| {
+            let yaml = serde_yaml_ng::to_string(resource).map_err(|_| fmt::Error)?;
+            write!(f, "---\n{}", yaml)
         })
     }
 }
 
+/// Parses a flattened key into the path of the secret definition, the namespace given by the
+/// layout (if any), the secret name, and the remaining (secret relative) key.
+///
+/// ```text
+/// secret.key => (secret, None, secret, key)
+/// secret._labels.app => (secret, None, secret, _labels.app)
+/// [secret.example.com].key => ([secret.example.com], None, secret.example.com, key)
+/// namespace.secret.key => (namespace.secret, Some(namespace), secret, key) (when namespaced)
+/// namespace.secret.key => (namespace, None, namespace, secret.key) (otherwise)
+/// ```
+fn parse_key(key: &str, namespaced: bool) -> (&str, Option<&str>, &str, &str) {
+    let (first, rest) = split_first_segment(key);
+    let (second, remainder) = split_first_segment(rest);
+
+    // Metadata keys are always prefixed, so with the namespace layout anything else nested one
+    // level deeper than a data key must be a secret within a namespace.
+    if namespaced && !remainder.is_empty() && !second.starts_with(METADATA_PREFIX) {
+        let path = &key[..first.len() + KEY_DELIMITER.len() + second.len()];
+        return (path, Some(unwrap_key(first)), unwrap_key(second), remainder);
+    }
+
+    (first, None, unwrap_key(first), rest)
+}
+
+/// Returns whether the key is part of a `_meta` object at the secret or namespace level, e.g.
+/// `_meta.app.owner` or `staging._meta.db.owner`, which would otherwise be mistaken for a secret
+/// named `_meta` (or one named after the namespace). `_meta` objects within a secret are ignored
+/// too, so they're skipped here as well.
+fn is_secret_meta(key: &str) -> bool {
+    let (first, rest) = split_first_segment(key);
+    first == META_KEY || split_first_segment(rest).0 == META_KEY
+}
+
+/// Formats the secret identifier as `namespace/name` (or just `name` without a namespace).
+fn display_id((name, namespace): SecretId) -> String {
+    match namespace {
+        Some(ns) => format!("{}/{}", ns, name),
+        None => name.to_string(),
+    }
+}
+
+/// Strips the square brackets used to escape keys containing dots.
+fn unwrap_key(key: &str) -> &str {
+    key.trim_matches(&['[', ']'] as &[_])
+}
+
+/// Returns the key wrapped in square brackets when it contains dots, matching [crate::SecretsMap].
+fn wrap_key(key: &str) -> String {
+    match key.contains(KEY_DELIMITER) {
+        true => format!("[{}]", key),
+        false => key.to_string(),
+    }
+}
+
+/// Returns whether the name is a valid DNS-1123 subdomain.
+fn is_dns_subdomain(name: &str) -> bool {
+    name.len() <= MAX_NAME_LENGTH && NAME_PATTERN.is_match(name)
+}
+
+/// Returns whether the namespace is a valid DNS-1123 label.
+fn is_dns_label(namespace: &str) -> bool {
+    namespace.len() <= MAX_NAMESPACE_LENGTH && NAMESPACE_PATTERN.is_match(namespace)
+}
+
+/// Returns whether the key is valid for use in the data section of a secret.
+fn is_data_key(key: &str) -> bool {
+    key != "." && key != ".." && key.len() <= MAX_NAME_LENGTH && DATA_KEY_PATTERN.is_match(key)
+}
+
+/// Returns the union of both maps, preferring values from `overrides`.
+fn merge<'a>(base: &BTreeMap<&'a str, &'a str>, overrides: &BTreeMap<&'a str, &'a str>) -> BTreeMap<&'a str, &'a str> {
+    let mut merged = base.clone();
+    merged.extend(overrides.iter().map(|(k, v)| (*k, *v)));
+    merged
+}
+
+/// Converts the map into a JSON object, or [None] when it's empty.
+fn object<V: Into<Value>, I: IntoIterator<Item = (String, V)>>(entries: I) -> Option<Value> {
+    let map = entries.into_iter().map(|(k, v)| (k, v.into())).collect::<Map<_, _>>();
+    (!map.is_empty()).then_some(Value::Object(map))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,14 +516,23 @@ mod tests {
         let exp = BTreeMap::from([
             (
                 "credentials",
-                BTreeMap::from([("_namespace", "testing"), ("path", "/some/path/file.ext")]),
+                Secret {
+                    name: "credentials",
+                    namespace: Some("testing"),
+                    data: BTreeMap::from([("path", "/some/path/file.ext")]),
+                    ..Default::default()
+                },
             ),
             (
                 "database",
-                BTreeMap::from([
-                    ("DATABASE_URL", "pgsql://db_url"),
-                    ("READ_ONLY_DATABASE_URL", "pgsql://ro_db_url"),
-                ]),
+                Secret {
+                    name: "database",
+                    data: BTreeMap::from([
+                        ("DATABASE_URL", "pgsql://db_url"),
+                        ("READ_ONLY_DATABASE_URL", "pgsql://ro_db_url"),
+                    ]),
+                    ..Default::default()
+                },
             ),
         ]);
 
@@ -111,22 +553,330 @@ mod tests {
         let exp = BTreeMap::from([
             (
                 "credentials",
-                BTreeMap::from([
-                    ("_namespace", "testing"),
-                    ("_type", "kubernetes.io/tls"),
-                    ("path", "/some/path/file.ext"),
-                ]),
+                Secret {
+                    name: "credentials",
+                    namespace: Some("testing"),
+                    kind: Some("kubernetes.io/tls"),
+                    data: BTreeMap::from([("path", "/some/path/file.ext")]),
+                    ..Default::default()
+                },
             ),
             (
                 "database",
-                BTreeMap::from([
-                    ("DATABASE_URL", "pgsql://db_url"),
-                    ("READ_ONLY_DATABASE_URL", "pgsql://ro_db_url"),
-                ]),
+                Secret {
+                    name: "database",
+                    data: BTreeMap::from([
+                        ("DATABASE_URL", "pgsql://db_url"),
+                        ("READ_ONLY_DATABASE_URL", "pgsql://ro_db_url"),
+                    ]),
+                    ..Default::default()
+                },
             ),
         ]);
 
         let manifest = SecretsManifest::new(secrets);
         assert_eq!(exp, manifest.inner);
     }
+
+    #[test]
+    fn secret_with_metadata() {
+        let secrets = HashMap::from([
+            ("app.TOKEN", "abc"),
+            ("app._immutable", "true"),
+            ("app._labels.team", "platform"),
+            ("app._labels.[app.kubernetes.io/name]", "app"),
+            ("app._annotations.owner", "someone"),
+            ("app._meta.TOKEN.owner", "someone"),
+        ]);
+
+        let exp = Secret {
+            name: "app",
+            immutable: true,
+            labels: BTreeMap::from([("app.kubernetes.io/name", "app"), ("team", "platform")]),
+            annotations: BTreeMap::from([("owner", "someone")]),
+            data: BTreeMap::from([("TOKEN", "abc")]),
+            ..Default::default()
+        };
+
+        let manifest = SecretsManifest::new(secrets);
+        assert_eq!(Some(&exp), manifest.inner.get("app"));
+    }
+
+    #[test]
+    fn display_with_common_metadata() {
+        let secrets = HashMap::from([
+            ("app.TOKEN", "abc"),
+            ("app._immutable", "true"),
+            ("app._labels.team", "platform"),
+        ]);
+
+        let manifest = SecretsManifest::new(secrets)
+            .with_labels([("team", "default"), ("managed-by", "rejson")])
+            .with_annotations([("note", "generated")]);
+
+        let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: app
+  labels:
+    managed-by: rejson
+    team: platform
+  annotations:
+    note: generated
+immutable: true
+data:
+  TOKEN: YWJj
+"#;
+
+        assert_eq!(exp, manifest.to_string());
+    }
+
+    #[test]
+    fn display_string_data() {
+        let secrets = HashMap::from([("app.TOKEN", "abc"), ("app.[config.json]", "{\"a\": 1}")]);
+        let manifest = SecretsManifest::new(secrets).with_string_data(true);
+
+        let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: app
+stringData:
+  TOKEN: abc
+  config.json: '{"a": 1}'
+"#;
+
+        assert_eq!(exp, manifest.to_string());
+    }
+
+    #[test]
+    fn display_quotes_special_characters() {
+        let secrets = HashMap::from([("app.TOKEN", "abc"), ("app._namespace", "weird: #ns")]);
+        let manifest = SecretsManifest::new(secrets);
+
+        let yaml = manifest.to_string();
+        assert!(yaml.contains("  namespace: 'weird: #ns'\n"), "{}", yaml);
+    }
+
+    #[test]
+    fn dotted_secret_names() {
+        let secrets = HashMap::from([("[app.example.com].TOKEN", "abc"), ("[app.example.com].[a.b]", "def")]);
+        let manifest = SecretsManifest::new(secrets);
+
+        let secret = manifest.inner.get("[app.example.com]").unwrap();
+        assert_eq!("app.example.com", secret.name);
+        assert_eq!(BTreeMap::from([("TOKEN", "abc"), ("a.b", "def")]), secret.data);
+    }
+
+    #[test]
+    fn validate() {
+        let secrets = HashMap::from([("app.TOKEN", "abc"), ("[app.example.com].[file.json]", "{}")]);
+        assert!(SecretsManifest::new(secrets).validate().is_ok());
+
+        let secrets = HashMap::from([
+            ("Bad_Name.TOKEN", "abc"),
+            ("app.[bad key]", "abc"),
+            ("app.good-key", "abc"),
+        ]);
+
+        let err = SecretsManifest::new(secrets)
+            .with_root("kubernetes")
+            .validate()
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("kubernetes.Bad_Name: secret name"), "{}", err);
+        assert!(err.contains("kubernetes.app.bad key: key must"), "{}", err);
+        assert!(!err.contains("good-key"), "{}", err);
+
+        let secrets = HashMap::from([("app.TOKEN", "abc"), ("app._namespace", "../../escaped")]);
+        let err = SecretsManifest::new(secrets).validate().unwrap_err().to_string();
+        assert!(
+            err.contains("app: namespace ../../escaped must be a valid DNS-1123 label"),
+            "{}",
+            err
+        );
+
+        let secrets = HashMap::from([("app.TOKEN", "abc")]);
+        let err = SecretsManifest::new(secrets)
+            .with_namespace("a.b")
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("namespace a.b must be a valid DNS-1123 label"), "{}", err);
+    }
+
+    #[test]
+    fn meta_objects() {
+        let secrets = HashMap::from([
+            ("app.TOKEN", "abc"),
+            ("app._meta.TOKEN.owner", "me"),
+            ("_meta.app.owner", "me"),
+            ("staging.db.PASSWORD", "pass"),
+            ("staging._meta.db.owner", "me"),
+            ("_namespaces", "true"),
+        ]);
+
+        let manifest = SecretsManifest::new(secrets);
+        assert!(manifest.validate().is_ok());
+        assert_eq!(
+            vec!["app", "staging.db"],
+            manifest.inner.keys().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(BTreeMap::from([("TOKEN", "abc")]), manifest.inner["app"].data);
+    }
+
+    #[test]
+    fn namespaced_layout() {
+        let secrets = HashMap::from([
+            ("staging.db-creds.PASSWORD", "staging-pass"),
+            ("production.db-creds.PASSWORD", "production-pass"),
+            ("production.db-creds._labels.tier", "prod"),
+            ("other.KEY", "value"),
+        ]);
+
+        // Without the marker, nested objects are data keys of the top-level secret.
+        let manifest = SecretsManifest::new(secrets.clone());
+        let staging = manifest.inner.get("staging").unwrap();
+        assert_eq!(None, staging.namespace);
+        assert_eq!(BTreeMap::from([("db-creds.PASSWORD", "staging-pass")]), staging.data);
+
+        let mut secrets = secrets;
+        secrets.insert("_namespaces", "true");
+        let manifest = SecretsManifest::new(secrets);
+        assert!(manifest.validate().is_ok());
+        assert!(!manifest.inner.contains_key("_namespaces"));
+
+        let staging = manifest.inner.get("staging.db-creds").unwrap();
+        assert_eq!("db-creds", staging.name);
+        assert_eq!(Some("staging"), staging.namespace);
+        assert_eq!(BTreeMap::from([("PASSWORD", "staging-pass")]), staging.data);
+
+        let production = manifest.inner.get("production.db-creds").unwrap();
+        assert_eq!(Some("production"), production.namespace);
+        assert_eq!(BTreeMap::from([("tier", "prod")]), production.labels);
+
+        let other = manifest.inner.get("other").unwrap();
+        assert_eq!(None, other.namespace);
+
+        let ids = manifest.resolve().0.into_keys().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("db-creds", Some("production")),
+                ("db-creds", Some("staging")),
+                ("other", None),
+            ],
+            ids
+        );
+    }
+
+    #[test]
+    fn colliding_definitions() {
+        let secrets = HashMap::from([
+            ("db-creds.PASSWORD", "a"),
+            ("db-creds._namespace", "staging"),
+            ("staging.db-creds.PASSWORD", "b"),
+            ("_namespaces", "true"),
+        ]);
+
+        let manifest = SecretsManifest::new(secrets).with_root("kubernetes");
+        let err = manifest.validate().unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "kubernetes.staging.db-creds: secret staging/db-creds is already defined at kubernetes.db-creds"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn conflicting_namespace() {
+        let secrets = HashMap::from([
+            ("staging.db-creds.PASSWORD", "a"),
+            ("staging.db-creds._namespace", "other"),
+            ("_namespaces", "true"),
+        ]);
+
+        let err = SecretsManifest::new(secrets).validate().unwrap_err().to_string();
+        assert!(
+            err.contains("staging.db-creds._namespace: namespace other conflicts"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn namespace_filter_and_override() {
+        let secrets = HashMap::from([
+            ("staging.db-creds.PASSWORD", "a"),
+            ("production.db-creds.PASSWORD", "b"),
+            ("other.KEY", "value"),
+            ("_namespaces", "true"),
+        ]);
+
+        let manifest = SecretsManifest::new(secrets)
+            .with_namespace_filter(["staging"])
+            .with_namespace("preview");
+
+        assert!(manifest.validate().is_ok());
+
+        let ids = manifest.resolve().0.into_keys().collect::<Vec<_>>();
+        assert_eq!(vec![("db-creds", Some("preview"))], ids);
+
+        // Overriding the namespace of both definitions makes them collide.
+        let secrets = HashMap::from([
+            ("staging.db-creds.PASSWORD", "a"),
+            ("production.db-creds.PASSWORD", "b"),
+            ("_namespaces", "true"),
+        ]);
+        let manifest = SecretsManifest::new(secrets).with_namespace("preview");
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn base64_values() {
+        let secrets = HashMap::from([("app.TOKEN", "abc"), ("app.[keystore.p12]", "base64:3q2+7w==")]);
+
+        let manifest = SecretsManifest::new(secrets);
+        assert!(manifest.validate().is_ok());
+
+        let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: app
+data:
+  TOKEN: YWJj
+  keystore.p12: 3q2+7w==
+"#;
+        assert_eq!(exp, manifest.to_string());
+
+        let secrets = HashMap::from([("app.TOKEN", "abc"), ("app.[keystore.p12]", "base64:3q2+7w==")]);
+
+        let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: app
+stringData:
+  TOKEN: abc
+data:
+  keystore.p12: 3q2+7w==
+"#;
+        assert_eq!(exp, SecretsManifest::new(secrets).with_string_data(true).to_string());
+    }
+
+    #[test]
+    fn invalid_base64_values() {
+        let secrets = HashMap::from([("app.[keystore.p12]", "base64:not base64!")]);
+
+        let err = SecretsManifest::new(secrets).validate().unwrap_err().to_string();
+        assert!(
+            err.contains("app.[keystore.p12]: value has the base64: prefix"),
+            "{}",
+            err
+        );
+    }
 }
@@ -0,0 +1,386 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::keyed_hash;
+
+const SECRET_KIND: &str = "Secret";
+const YAML_EXTENSIONS: [&str; 2] = ["yaml", "yml"];
+
+/// The differences between existing Secret manifests and the generated ones, sorted by name and
+/// namespace. Values are never included, only a short keyed hash (HMAC-SHA512) of each, using a
+/// random key that's only used for this diff. Identical values still have identical hashes, but the
+/// hashes can't be compared to anything outside of this diff (e.g. a hash of a guessed value).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+    pub secrets: Vec<SecretDiff>,
+}
+
+/// The differences for a single secret.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SecretDiff {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub change: Change,
+    pub keys: Vec<KeyDiff>,
+}
+
+/// The difference for a single data key.
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeyDiff {
+    pub key: String,
+    pub change: Change,
+    /// The short keyed hash of the existing value, if any.
+    pub from: Option<String>,
+    /// The short keyed hash of the generated value, if any.
+    pub to: Option<String>,
+}
+
+/// How a secret or key differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ManifestDiff {
+    /// Returns whether there are no differences.
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// Returns the differences as JSON, with an object per secret.
+    ///
+    /// ```json
+    /// {
+    ///   "secrets": [{
+    ///     "name": "db",
+    ///     "namespace": "staging",
+    ///     "change": "changed",
+    ///     "keys": [{ "key": "PASSWORD", "change": "changed", "from": "2cf24dba", "to": "486ea462" }]
+    ///   }]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> Value {
+        let secrets = self
+            .secrets
+            .iter()
+            .map(|secret| {
+                let keys = secret
+                    .keys
+                    .iter()
+                    .map(|k| json!({ "key": k.key, "change": k.change.as_str(), "from": k.from, "to": k.to }))
+                    .collect::<Vec<_>>();
+
+                json!({
+                    "name": secret.name,
+                    "namespace": secret.namespace,
+                    "change": secret.change.as_str(),
+                    "keys": keys,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({ "secrets": secrets })
+    }
+}
+
+impl Change {
+    /// Returns the name used in JSON reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        }
+    }
+}
+
+impl fmt::Display for ManifestDiff {
+    /// Writes one line per secret followed by an indented line per key.
+    ///
+    /// ```text
+    /// ~ staging/db
+    ///   + USER (5d41402a)
+    ///   ~ PASSWORD (2cf24dba -> 486ea462)
+    /// - old-secret
+    ///   - TOKEN (9f86d081)
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.secrets.iter().try_for_each(|secret| {
+            match &secret.namespace {
+                Some(ns) => writeln!(f, "{} {}/{}", secret.change, ns, secret.name)?,
+                None => writeln!(f, "{} {}", secret.change, secret.name)?,
+            }
+
+            secret.keys.iter().try_for_each(|key| match (&key.from, &key.to) {
+                (Some(from), Some(to)) => writeln!(f, "  {} {} ({} -> {})", key.change, key.key, from, to),
+                (Some(hash), None) | (None, Some(hash)) => writeln!(f, "  {} {} ({})", key.change, key.key, hash),
+                (None, None) => writeln!(f, "  {} {}", key.change, key.key),
+            })
+        })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Change::Added => "+",
+            Change::Removed => "-",
+            Change::Changed => "~",
+        })
+    }
+}
+
+/// Reads every Secret from the YAML file, or from the YAML files under the directory (recursively).
+/// Other kinds of resources are ignored.
+pub(super) fn read(path: &Path) -> Result<Vec<Value>> {
+    let mut files = vec![];
+    find_yaml(path, &mut files)?;
+
+    files.iter().try_fold(vec![], |mut secrets, file| {
+        let yaml = fs::read_to_string(file).with_context(|| format!("reading {}", file.display()))?;
+
+        for doc in serde_yaml_ng::Deserializer::from_str(&yaml) {
+            let value = Value::deserialize(doc).with_context(|| format!("parsing {}", file.display()))?;
+            if value["kind"] == SECRET_KIND {
+                secrets.push(value);
+            }
+        }
+
+        Ok(secrets)
+    })
+}
+
+/// Compares the existing secrets to the generated ones, hashing values with a random key.
+pub(super) fn compare(existing: Vec<Value>, generated: Vec<Value>) -> ManifestDiff {
+    let mut key = [0u8; 32];
+    rand::rng().fill_bytes(&mut key);
+
+    compare_with_key(existing, generated, &key)
+}
+
+/// Compares the existing secrets to the generated ones, hashing values with the supplied key.
+fn compare_with_key(existing: Vec<Value>, generated: Vec<Value>, hash_key: &[u8]) -> ManifestDiff {
+    let existing = by_id(existing);
+    let generated = by_id(generated);
+
+    let secrets = existing
+        .keys()
+        .chain(generated.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|id| {
+            let from = existing.get(id);
+            let to = generated.get(id);
+            let empty = BTreeMap::new();
+
+            let keys = from
+                .unwrap_or(&empty)
+                .keys()
+                .chain(to.unwrap_or(&empty).keys())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter_map(|key| {
+                    let from = from.and_then(|data| data.get(key));
+                    let to = to.and_then(|data| data.get(key));
+
+                    let change = match (from, to) {
+                        (Some(from), Some(to)) if from == to => return None,
+                        (Some(_), Some(_)) => Change::Changed,
+                        (Some(_), None) => Change::Removed,
+                        _ => Change::Added,
+                    };
+
+                    Some(KeyDiff {
+                        key: key.to_string(),
+                        change,
+                        from: from.map(|v| keyed_hash(hash_key, v)),
+                        to: to.map(|v| keyed_hash(hash_key, v)),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let change = match (from, to) {
+                (Some(_), None) => Change::Removed,
+                (None, Some(_)) => Change::Added,
+                _ if keys.is_empty() => return None,
+                _ => Change::Changed,
+            };
+
+            Some(SecretDiff {
+                name: id.0.to_string(),
+                namespace: id.1.clone(),
+                change,
+                keys,
+            })
+        })
+        .collect();
+
+    ManifestDiff { secrets }
+}
+
+/// Indexes the secrets by name and namespace, mapping each to its (decoded) data. Like Kubernetes,
+/// `stringData` takes precedence over `data`.
+fn by_id(secrets: Vec<Value>) -> BTreeMap<(String, Option<String>), BTreeMap<String, Vec<u8>>> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+
+    secrets
+        .into_iter()
+        .map(|secret| {
+            let metadata = &secret["metadata"];
+            let id = (
+                metadata["name"].as_str().unwrap_or_default().to_string(),
+                metadata["namespace"].as_str().map(String::from),
+            );
+
+            let mut data = BTreeMap::new();
+            if let Some(values) = secret["data"].as_object() {
+                values.iter().for_each(|(k, v)| {
+                    let v = v.as_str().unwrap_or_default();
+                    data.insert(k.to_string(), b64.decode(v).unwrap_or_else(|_| v.as_bytes().to_vec()));
+                });
+            }
+
+            if let Some(values) = secret["stringData"].as_object() {
+                values.iter().for_each(|(k, v)| {
+                    data.insert(k.to_string(), v.as_str().unwrap_or_default().as_bytes().to_vec());
+                });
+            }
+
+            (id, data)
+        })
+        .collect()
+}
+
+/// Collects the YAML files at `path` (either the file itself or the files under the directory).
+fn find_yaml(path: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        found.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)
+        .with_context(|| format!("reading {}", path.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    entries.into_iter().try_for_each(|entry| {
+        if entry.is_dir() {
+            return find_yaml(&entry, found);
+        }
+
+        if entry
+            .extension()
+            .is_some_and(|ext| YAML_EXTENSIONS.iter().any(|yaml| ext == *yaml))
+        {
+            found.push(entry);
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn secret(name: &str, namespace: Option<&str>, data: Value) -> Value {
+        let mut secret = json!({ "kind": "Secret", "metadata": { "name": name }, "data": data });
+        if let Some(ns) = namespace {
+            secret["metadata"]["namespace"] = ns.into();
+        }
+
+        secret
+    }
+
+    #[test]
+    fn compare_secrets() {
+        let existing = vec![
+            secret("app", None, json!({ "TOKEN": "YWJj", "OLD": "eA==" })),
+            secret("db", Some("staging"), json!({ "PASSWORD": "cHc=" })),
+            secret("gone", None, json!({ "KEY": "eA==" })),
+        ];
+
+        let mut db = secret("db", Some("staging"), json!({}));
+        db["stringData"] = json!({ "PASSWORD": "pw" });
+
+        let generated = vec![
+            secret("app", None, json!({ "TOKEN": "eHl6", "NEW": "eA==" })),
+            db,
+            secret("new", None, json!({ "KEY": "eA==" })),
+        ];
+
+        let diff = compare_with_key(existing.clone(), generated.clone(), b"key");
+        let hash = keyed_hash(b"key", b"x");
+
+        assert_eq!(
+            format!(
+                "~ app\n  + NEW ({hash})\n  - OLD ({hash})\n  ~ TOKEN ({} -> {})\n- gone\n  - KEY ({hash})\n+ new\n  + KEY ({hash})\n",
+                keyed_hash(b"key", b"abc"),
+                keyed_hash(b"key", b"xyz"),
+            ),
+            diff.to_string()
+        );
+
+        let json = diff.to_json();
+        assert_eq!("gone", json["secrets"][1]["name"]);
+        assert_eq!("removed", json["secrets"][1]["change"]);
+        assert_eq!(
+            json!({ "key": "TOKEN", "change": "changed", "from": keyed_hash(b"key", b"abc"), "to": keyed_hash(b"key", b"xyz") }),
+            json["secrets"][0]["keys"][2]
+        );
+
+        // Every diff uses its own key, so hashes can't be compared across diffs.
+        let (first, second) = (
+            compare(existing.clone(), generated.clone()),
+            compare(existing, generated),
+        );
+        assert_eq!(first.secrets[0].keys[0].to, first.secrets[2].keys[0].to);
+        assert_ne!(first.secrets[0].keys[0].to, second.secrets[0].keys[0].to);
+    }
+
+    #[test]
+    fn compare_unchanged() {
+        let existing = vec![secret("app", None, json!({ "TOKEN": "YWJj" }))];
+        let generated = existing.clone();
+
+        assert!(compare(existing, generated).is_empty());
+    }
+
+    #[test]
+    fn read_manifests() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        fs::create_dir(dir.path().join("staging"))?;
+        fs::write(
+            dir.path().join("secrets.yaml"),
+            "kind: Secret\nmetadata:\n  name: a\n---\nkind: ConfigMap\nmetadata:\n  name: b\n",
+        )?;
+        fs::write(
+            dir.path().join("staging/db.yml"),
+            "kind: Secret\nmetadata:\n  name: db\n",
+        )?;
+        fs::write(dir.path().join("README.md"), "not yaml: [")?;
+
+        let secrets = read(dir.path())?;
+        assert_eq!(2, secrets.len());
+        assert_eq!("a", secrets[0]["metadata"]["name"]);
+        assert_eq!("db", secrets[1]["metadata"]["name"]);
+
+        assert!(read(&dir.path().join("missing.yaml")).is_err());
+        Ok(())
+    }
+}
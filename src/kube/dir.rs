@@ -0,0 +1,279 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde_json::{Value, json};
+
+/// Annotation added to every secret written to a directory, holding the path of the source file it
+/// was generated from, relative to the directory. This is how stale files from earlier runs are
+/// found, regardless of the working directory rejson was run from.
+pub const SOURCE_ANNOTATION: &str = "rejson.pseudomuto.com/source";
+
+const KUSTOMIZATION_FILE: &str = "kustomization.yaml";
+const YAML_EXTENSION: &str = "yaml";
+
+/// The outcome of writing files to a directory. Paths are relative to the directory.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DirSummary {
+    /// Files that were created or updated.
+    pub written: Vec<PathBuf>,
+    /// Files whose contents were already up to date.
+    pub unchanged: Vec<PathBuf>,
+    /// Files from an earlier run that no longer correspond to a secret.
+    pub removed: Vec<PathBuf>,
+}
+
+/// Writes each resource to `<namespace>/<name>.yaml` (or `<name>.yaml` without a namespace) under
+/// the supplied directory, removing stale files previously generated from the same source.
+pub(super) fn write(dir: &Path, source: &Path, resources: Vec<Value>, kustomization: bool) -> Result<DirSummary> {
+    let mut summary = DirSummary::default();
+    let mut generated = vec![];
+
+    fs::create_dir_all(dir)?;
+    let annotation = source_annotation(dir, source)?;
+
+    // Earlier versions stored the path as given on the command line.
+    let legacy_annotation = source.to_string_lossy();
+    let legacy_annotation = legacy_annotation.trim_start_matches("./");
+
+    resources.into_iter().try_for_each(|mut resource| -> Result<()> {
+        resource["metadata"]["annotations"][SOURCE_ANNOTATION] = annotation.as_str().into();
+
+        let path = resource_path(&resource);
+        let yaml = serde_yaml_ng::to_string(&resource)?;
+        let full_path = dir.join(&path);
+
+        match fs::read_to_string(&full_path) {
+            Ok(existing) if existing == yaml => summary.unchanged.push(path.clone()),
+            _ => {
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                fs::write(&full_path, yaml)?;
+                summary.written.push(path.clone());
+            }
+        }
+
+        generated.push(path);
+        Ok(())
+    })?;
+
+    let mut existing = vec![];
+    find_generated(dir, dir, &mut existing)?;
+
+    existing
+        .iter()
+        .filter(|(path, from)| (*from == annotation || from == legacy_annotation) && !generated.contains(path))
+        .try_for_each(|(path, _)| -> Result<()> {
+            fs::remove_file(dir.join(path))?;
+            summary.removed.push(path.clone());
+
+            // Clean up namespace directories that no longer contain anything.
+            if let Some(parent) = dir.join(path).parent()
+                && parent != dir
+                && fs::read_dir(parent)?.next().is_none()
+            {
+                fs::remove_dir(parent)?;
+            }
+
+            Ok(())
+        })?;
+
+    if kustomization {
+        let mut resources = existing
+            .into_iter()
+            .map(|(path, _)| path)
+            .filter(|path| !summary.removed.contains(path))
+            .chain(generated)
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .collect::<Vec<_>>();
+        resources.sort();
+        resources.dedup();
+
+        let yaml = serde_yaml_ng::to_string(&json!({
+            "apiVersion": "kustomize.config.k8s.io/v1beta1",
+            "kind": "Kustomization",
+            "resources": resources,
+        }))?;
+
+        let path = PathBuf::from(KUSTOMIZATION_FILE);
+        match fs::read_to_string(dir.join(&path)) {
+            Ok(existing) if existing == yaml => summary.unchanged.push(path),
+            _ => {
+                fs::write(dir.join(&path), yaml)?;
+                summary.written.push(path);
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Returns the path of `source` relative to `dir`, using `/` as the separator.
+fn source_annotation(dir: &Path, source: &Path) -> Result<String> {
+    let absolute = |path: &Path| -> Result<PathBuf> {
+        if let Ok(path) = fs::canonicalize(path) {
+            return Ok(path);
+        }
+
+        // The path doesn't exist (yet), so resolve `..` without following symlinks.
+        Ok(std::path::absolute(path)?
+            .components()
+            .fold(PathBuf::new(), |mut path, component| {
+                match component {
+                    Component::ParentDir => _ = path.pop(),
+                    component => path.push(component),
+                }
+                path
+            }))
+    };
+    let (dir, source) = (absolute(dir)?, absolute(source)?);
+
+    let dir_components = dir.components().collect::<Vec<_>>();
+    let source_components = source.components().collect::<Vec<_>>();
+    let common = dir_components
+        .iter()
+        .zip(&source_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let relative = dir_components[common..]
+        .iter()
+        .map(|_| "..".to_string())
+        .chain(
+            source_components[common..]
+                .iter()
+                .filter_map(|component| match component {
+                    Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                    _ => None,
+                }),
+        )
+        .collect::<Vec<_>>();
+
+    Ok(relative.join("/"))
+}
+
+/// Returns the path (relative to the output directory) for the resource.
+fn resource_path(resource: &Value) -> PathBuf {
+    let metadata = &resource["metadata"];
+    let file = format!("{}.{}", metadata["name"].as_str().unwrap_or_default(), YAML_EXTENSION);
+
+    match metadata["namespace"].as_str() {
+        Some(ns) => Path::new(ns).join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+/// Recursively finds the YAML files under `dir` that were generated by rejson, returning their
+/// paths (relative to `root`) and the source annotation.
+fn find_generated(root: &Path, dir: &Path, found: &mut Vec<(PathBuf, String)>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    fs::read_dir(dir)?.try_for_each(|entry| -> Result<()> {
+        let path = entry?.path();
+        if path.is_dir() {
+            return find_generated(root, &path, found);
+        }
+
+        if path.extension().is_none_or(|ext| ext != YAML_EXTENSION) {
+            return Ok(());
+        }
+
+        let source = fs::read_to_string(&path)
+            .ok()
+            .and_then(|yaml| serde_yaml_ng::from_str::<Value>(&yaml).ok())
+            .and_then(|value| {
+                value["metadata"]["annotations"][SOURCE_ANNOTATION]
+                    .as_str()
+                    .map(String::from)
+            });
+
+        if let Some(source) = source {
+            found.push((path.strip_prefix(root)?.to_path_buf(), source));
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(name: &str, namespace: Option<&str>) -> Value {
+        let mut secret = json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": { "name": name },
+            "data": { "key": "dmFsdWU=" },
+        });
+
+        if let Some(ns) = namespace {
+            secret["metadata"]["namespace"] = ns.into();
+        }
+
+        secret
+    }
+
+    #[test]
+    fn write_and_clean() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let resources = vec![secret("app", None), secret("db", Some("staging"))];
+
+        let a = dir.path().join("a.ejson");
+        let b = dir.path().join("b.ejson");
+
+        let summary = write(dir.path(), &a, resources.clone(), false)?;
+        assert_eq!(
+            vec![PathBuf::from("app.yaml"), PathBuf::from("staging/db.yaml")],
+            summary.written
+        );
+
+        let summary = write(dir.path(), &a, resources, false)?;
+        assert!(summary.written.is_empty());
+        assert_eq!(2, summary.unchanged.len());
+
+        // files from other sources are left alone
+        write(dir.path(), &b, vec![secret("other", None)], false)?;
+
+        let summary = write(dir.path(), &a, vec![secret("app", None)], true)?;
+        assert_eq!(vec![PathBuf::from("staging/db.yaml")], summary.removed);
+        assert!(dir.path().join("other.yaml").exists());
+        assert!(!dir.path().join("staging").exists());
+
+        let kustomization = fs::read_to_string(dir.path().join(KUSTOMIZATION_FILE))?;
+        assert!(kustomization.contains("resources:\n- app.yaml\n- other.yaml\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn source_is_relative_to_dir() -> Result<()> {
+        let root = assert_fs::TempDir::new()?;
+        let dir = root.path().join("out");
+        let source = root.path().join("config").join("secrets.ejson");
+
+        assert_eq!("../config/secrets.ejson", source_annotation(&dir, &source)?);
+        assert_eq!(
+            "../config/secrets.ejson",
+            source_annotation(&dir, &dir.join("../config/secrets.ejson"))?
+        );
+        assert_eq!(
+            "secrets.ejson",
+            source_annotation(root.path(), &source.with_file_name("../secrets.ejson"))?
+        );
+
+        // the same source reached through a different path replaces the earlier files
+        let resources = vec![secret("app", None)];
+        write(&dir, &source, resources.clone(), false)?;
+        let summary = write(&dir, &dir.join("../config/secrets.ejson"), vec![], false)?;
+        assert_eq!(vec![PathBuf::from("app.yaml")], summary.removed);
+
+        Ok(())
+    }
+}
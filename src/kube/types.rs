@@ -0,0 +1,410 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use base64::Engine;
+use serde_json::{Map, Value, json};
+use x509_parser::{
+    der_parser::{
+        ber::{BerObject, BerObjectContent, Class},
+        parse_der,
+    },
+    pem::Pem,
+    public_key::PublicKey,
+};
+
+const DOCKER_CONFIG_JSON: &str = "kubernetes.io/dockerconfigjson";
+const TLS: &str = "kubernetes.io/tls";
+const BASIC_AUTH: &str = "kubernetes.io/basic-auth";
+const SSH_AUTH: &str = "kubernetes.io/ssh-auth";
+
+const DOCKER_CONFIG_KEY: &str = ".dockerconfigjson";
+const DOCKER_SERVER_KEY: &str = "server";
+const DOCKER_EMAIL_KEY: &str = "email";
+const USERNAME_KEY: &str = "username";
+const PASSWORD_KEY: &str = "password";
+const TLS_CERT_KEY: &str = "tls.crt";
+const TLS_KEY_KEY: &str = "tls.key";
+const SSH_KEY_KEY: &str = "ssh-privatekey";
+
+// Object identifiers for the private key algorithms we can match against a certificate.
+const RSA_OID: &str = "1.2.840.113549.1.1.1";
+const EC_OID: &str = "1.2.840.10045.2.1";
+const ED25519_OID: &str = "1.3.101.112";
+
+/// A problem with a secret of a well-known type. The key is the offending data key, or [None] when
+/// the problem is with the secret as a whole (e.g. a missing field).
+pub(super) type TypeError<'a> = (Option<&'a str>, String);
+
+/// Returns the data for a secret of the given type, validating required fields and generating any
+/// derived keys. Secrets without a well-known type are returned as is.
+///
+/// - `kubernetes.io/dockerconfigjson`: `server`, `username`, `password` (and optionally `email`) are replaced with a
+///   generated `.dockerconfigjson`, unless one is supplied directly.
+/// - `kubernetes.io/tls`: requires `tls.crt` and `tls.key`. When either is PEM encoded, both must be and the key must
+///   match the certificate.
+/// - `kubernetes.io/basic-auth`: requires `username` and/or `password`.
+/// - `kubernetes.io/ssh-auth`: requires a PEM encoded `ssh-privatekey`.
+///
+/// PEM values that were compacted by `encrypt` (newlines replaced with `\n`) are expanded again.
+pub(super) fn data<'a>(
+    kind: Option<&str>,
+    data: &BTreeMap<&'a str, &'a str>,
+) -> Result<BTreeMap<&'a str, Cow<'a, str>>, Vec<TypeError<'a>>> {
+    let mut result = data
+        .iter()
+        .map(|(k, v)| (*k, Cow::Borrowed(*v)))
+        .collect::<BTreeMap<_, _>>();
+
+    match kind {
+        Some(DOCKER_CONFIG_JSON) => docker_config(&mut result)?,
+        Some(TLS) => tls(&mut result)?,
+        Some(BASIC_AUTH) => basic_auth(&result)?,
+        Some(SSH_AUTH) => ssh_auth(&mut result)?,
+        _ => {}
+    }
+
+    Ok(result)
+}
+
+fn docker_config<'a>(data: &mut BTreeMap<&'a str, Cow<'a, str>>) -> Result<(), Vec<TypeError<'a>>> {
+    if data.contains_key(DOCKER_CONFIG_KEY) {
+        return Ok(());
+    }
+
+    require(
+        data,
+        DOCKER_CONFIG_JSON,
+        &[DOCKER_SERVER_KEY, USERNAME_KEY, PASSWORD_KEY],
+    )?;
+
+    let server = data.remove(DOCKER_SERVER_KEY).unwrap_or_default();
+    let username = data.remove(USERNAME_KEY).unwrap_or_default();
+    let password = data.remove(PASSWORD_KEY).unwrap_or_default();
+    let auth = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+
+    let mut entry = Map::new();
+    entry.insert(USERNAME_KEY.into(), username.into());
+    entry.insert(PASSWORD_KEY.into(), password.into());
+    if let Some(email) = data.remove(DOCKER_EMAIL_KEY) {
+        entry.insert(DOCKER_EMAIL_KEY.into(), email.into());
+    }
+    entry.insert("auth".into(), auth.into());
+
+    let config = json!({ "auths": { server.as_ref(): Value::Object(entry) } });
+    data.insert(DOCKER_CONFIG_KEY, config.to_string().into());
+    Ok(())
+}
+
+fn tls<'a>(data: &mut BTreeMap<&'a str, Cow<'a, str>>) -> Result<(), Vec<TypeError<'a>>> {
+    require(data, TLS, &[TLS_CERT_KEY, TLS_KEY_KEY])?;
+
+    expand_pem(data, TLS_CERT_KEY);
+    expand_pem(data, TLS_KEY_KEY);
+
+    // Values that aren't PEM at all (e.g. base64: encoded or placeholder values) can't be checked.
+    if !is_pem(&data[TLS_CERT_KEY]) && !is_pem(&data[TLS_KEY_KEY]) {
+        return Ok(());
+    }
+
+    verify_key_pair(&data[TLS_CERT_KEY], &data[TLS_KEY_KEY]).map_err(|e| vec![e])
+}
+
+fn basic_auth<'a>(data: &BTreeMap<&'a str, Cow<'a, str>>) -> Result<(), Vec<TypeError<'a>>> {
+    match data.contains_key(USERNAME_KEY) || data.contains_key(PASSWORD_KEY) {
+        true => Ok(()),
+        false => Err(vec![(
+            None,
+            format!(
+                "{} secrets require {} and/or {}",
+                BASIC_AUTH, USERNAME_KEY, PASSWORD_KEY
+            ),
+        )]),
+    }
+}
+
+fn ssh_auth<'a>(data: &mut BTreeMap<&'a str, Cow<'a, str>>) -> Result<(), Vec<TypeError<'a>>> {
+    require(data, SSH_AUTH, &[SSH_KEY_KEY])?;
+    expand_pem(data, SSH_KEY_KEY);
+
+    match Pem::iter_from_buffer(data[SSH_KEY_KEY].as_bytes()).next() {
+        Some(Ok(pem)) if pem.label.ends_with("PRIVATE KEY") => Ok(()),
+        _ => Err(vec![(Some(SSH_KEY_KEY), "must be a PEM encoded private key".into())]),
+    }
+}
+
+/// Ensures all of the supplied keys are present in the data.
+fn require<'a>(data: &BTreeMap<&'a str, Cow<'a, str>>, kind: &str, keys: &[&str]) -> Result<(), Vec<TypeError<'a>>> {
+    let errors = keys
+        .iter()
+        .filter(|key| !data.contains_key(*key))
+        .map(|key| (None, format!("{} secrets require {}", kind, key)))
+        .collect::<Vec<_>>();
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+/// Expands PEM values that were compacted into a single line (see [crate::compact]).
+fn expand_pem(data: &mut BTreeMap<&str, Cow<str>>, key: &str) {
+    if let Some(value) = data.get_mut(key)
+        && !value.contains('\n')
+        && value.contains(r"\n")
+    {
+        *value = value.replace(r"\r", "").replace(r"\n", "\n").into();
+    }
+}
+
+/// Returns whether the value looks like PEM, i.e. contains an encapsulation boundary.
+fn is_pem(value: &str) -> bool {
+    value.contains("-----BEGIN ")
+}
+
+/// Ensures the PEM encoded private key belongs to the PEM encoded certificate.
+fn verify_key_pair<'a>(cert: &str, key: &str) -> Result<(), TypeError<'a>> {
+    let cert_error = || (Some(TLS_CERT_KEY), "must be a PEM encoded certificate".to_string());
+    let key_error = || (Some(TLS_KEY_KEY), "must be a PEM encoded private key".to_string());
+
+    let cert_pem = match Pem::iter_from_buffer(cert.as_bytes()).next() {
+        Some(Ok(pem)) if pem.label == "CERTIFICATE" => pem,
+        _ => return Err(cert_error()),
+    };
+
+    let cert = cert_pem.parse_x509().map_err(|_| cert_error())?;
+
+    let key_pem = match Pem::iter_from_buffer(key.as_bytes()).next() {
+        Some(Ok(pem)) => pem,
+        _ => return Err(key_error()),
+    };
+
+    let public = public_key(&key_pem.label, &key_pem.contents).ok_or_else(|| {
+        (
+            Some(TLS_KEY_KEY),
+            "unsupported private key (expected RSA, EC or Ed25519)".to_string(),
+        )
+    })?;
+
+    let spki = cert.public_key();
+    let matches = match (spki.parsed(), public) {
+        (Ok(PublicKey::RSA(rsa)), KeyPublic::Rsa(modulus, exponent)) => {
+            trim(rsa.modulus) == trim(&modulus) && trim(rsa.exponent) == trim(&exponent)
+        }
+        (_, KeyPublic::Raw(bytes)) => spki.subject_public_key.data.as_ref() == bytes.as_slice(),
+        _ => false,
+    };
+
+    match matches {
+        true => Ok(()),
+        false => Err((Some(TLS_KEY_KEY), "private key does not match the certificate".into())),
+    }
+}
+
+/// The public part of a private key, in the form it appears in a certificate.
+enum KeyPublic {
+    /// An RSA modulus and public exponent.
+    Rsa(Vec<u8>, Vec<u8>),
+    /// The raw public key (EC point or Ed25519 key).
+    Raw(Vec<u8>),
+}
+
+/// Extracts the public key from a DER encoded private key with the given PEM label.
+fn public_key(label: &str, der: &[u8]) -> Option<KeyPublic> {
+    match label {
+        "RSA PRIVATE KEY" => rsa_public_key(der),
+        "EC PRIVATE KEY" => ec_public_key(der),
+        "PRIVATE KEY" => pkcs8_public_key(der),
+        _ => None,
+    }
+}
+
+/// PrivateKeyInfo ::= SEQUENCE { version, algorithm AlgorithmIdentifier, privateKey OCTET STRING }
+fn pkcs8_public_key(der: &[u8]) -> Option<KeyPublic> {
+    let (_, info) = parse_der(der).ok()?;
+    let info = info.as_sequence().ok()?;
+    let algorithm = info.get(1)?.as_sequence().ok()?.first()?.as_oid().ok()?.to_id_string();
+    let private_key = info.get(2)?.as_slice().ok()?;
+
+    match algorithm.as_str() {
+        RSA_OID => rsa_public_key(private_key),
+        EC_OID => ec_public_key(private_key),
+        ED25519_OID => {
+            // CurvePrivateKey ::= OCTET STRING (the 32 byte seed)
+            let (_, seed) = parse_der(private_key).ok()?;
+            Some(KeyPublic::Raw(
+                nacl::sign::generate_keypair(seed.as_slice().ok()?).pkey.to_vec(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// RSAPrivateKey ::= SEQUENCE { version, modulus INTEGER, publicExponent INTEGER, ... }
+fn rsa_public_key(der: &[u8]) -> Option<KeyPublic> {
+    let (_, key) = parse_der(der).ok()?;
+    let key = key.as_sequence().ok()?;
+    Some(KeyPublic::Rsa(
+        key.get(1)?.as_slice().ok()?.to_vec(),
+        key.get(2)?.as_slice().ok()?.to_vec(),
+    ))
+}
+
+/// ECPrivateKey ::= SEQUENCE { version, privateKey, parameters [0] OPTIONAL, publicKey [1] BIT STRING
+/// OPTIONAL }
+fn ec_public_key(der: &[u8]) -> Option<KeyPublic> {
+    let (_, key) = parse_der(der).ok()?;
+    key.as_sequence()
+        .ok()?
+        .iter()
+        .find(|obj| obj.header.class() == Class::ContextSpecific && obj.header.tag().0 == 1)
+        .and_then(explicit_bitstring)
+        .map(KeyPublic::Raw)
+}
+
+/// Returns the data of the BIT STRING wrapped in the explicitly tagged object.
+fn explicit_bitstring(obj: &BerObject) -> Option<Vec<u8>> {
+    let inner = match &obj.content {
+        BerObjectContent::Tagged(_, _, inner) => inner.as_bitstring().ok()?.data.to_vec(),
+        BerObjectContent::Unknown(any) => parse_der(any.data).ok()?.1.as_bitstring().ok()?.data.to_vec(),
+        _ => return None,
+    };
+
+    Some(inner)
+}
+
+/// Strips leading zeros from a big-endian integer.
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EC_CERT: &str = include_str!("../../tests/data/tls/ec.crt");
+    const EC_KEY: &str = include_str!("../../tests/data/tls/ec.key");
+    const EC_PKCS8_KEY: &str = include_str!("../../tests/data/tls/ec-pkcs8.key");
+    const OTHER_CERT: &str = include_str!("../../tests/data/tls/other.crt");
+    const RSA_CERT: &str = include_str!("../../tests/data/tls/rsa.crt");
+    const RSA_KEY: &str = include_str!("../../tests/data/tls/rsa.key");
+    const RSA_PKCS8_KEY: &str = include_str!("../../tests/data/tls/rsa-pkcs8.key");
+    const ED25519_CERT: &str = include_str!("../../tests/data/tls/ed25519.crt");
+    const ED25519_KEY: &str = include_str!("../../tests/data/tls/ed25519.key");
+
+    #[test]
+    fn untyped() {
+        let data = BTreeMap::from([("KEY", "value")]);
+        assert_eq!(
+            BTreeMap::from([("KEY", Cow::Borrowed("value"))]),
+            super::data(None, &data).unwrap()
+        );
+    }
+
+    #[test]
+    fn docker_config() {
+        let data = BTreeMap::from([
+            ("server", "registry.example.com"),
+            ("username", "user"),
+            ("password", "pass"),
+        ]);
+
+        let result = super::data(Some(DOCKER_CONFIG_JSON), &data).unwrap();
+        assert_eq!(
+            r#"{"auths":{"registry.example.com":{"username":"user","password":"pass","auth":"dXNlcjpwYXNz"}}}"#,
+            result[".dockerconfigjson"]
+        );
+        assert_eq!(1, result.len());
+
+        let data = BTreeMap::from([("server", "registry.example.com")]);
+        let errors = super::data(Some(DOCKER_CONFIG_JSON), &data).unwrap_err();
+        assert_eq!(2, errors.len());
+        assert!(errors[0].1.contains("require username"));
+    }
+
+    #[test]
+    fn tls_key_pairs() {
+        [
+            (EC_CERT, EC_KEY),
+            (EC_CERT, EC_PKCS8_KEY),
+            (RSA_CERT, RSA_KEY),
+            (RSA_CERT, RSA_PKCS8_KEY),
+            (ED25519_CERT, ED25519_KEY),
+        ]
+        .into_iter()
+        .for_each(|(cert, key)| {
+            let data = BTreeMap::from([("tls.crt", cert), ("tls.key", key)]);
+            assert!(super::data(Some(TLS), &data).is_ok(), "{}", cert);
+        });
+
+        [
+            (OTHER_CERT, EC_KEY),
+            (RSA_CERT, EC_KEY),
+            (EC_CERT, RSA_KEY),
+            (ED25519_CERT, EC_KEY),
+        ]
+        .into_iter()
+        .for_each(|(cert, key)| {
+            let data = BTreeMap::from([("tls.crt", cert), ("tls.key", key)]);
+            let errors = super::data(Some(TLS), &data).unwrap_err();
+            assert_eq!(
+                vec![(
+                    Some("tls.key"),
+                    "private key does not match the certificate".to_string()
+                )],
+                errors
+            );
+        });
+    }
+
+    #[test]
+    fn tls_invalid() {
+        let data = BTreeMap::from([("tls.crt", "nope"), ("tls.key", EC_KEY)]);
+        let errors = super::data(Some(TLS), &data).unwrap_err();
+        assert_eq!(Some("tls.crt"), errors[0].0);
+
+        let data = BTreeMap::from([("tls.crt", EC_CERT), ("tls.key", "nope")]);
+        let errors = super::data(Some(TLS), &data).unwrap_err();
+        assert_eq!(Some("tls.key"), errors[0].0);
+
+        // Neither value is PEM, so there's nothing to verify.
+        let data = BTreeMap::from([("tls.crt", "base64:Y2VydA=="), ("tls.key", "base64:a2V5")]);
+        assert!(super::data(Some(TLS), &data).is_ok());
+
+        let data = BTreeMap::from([("tls.crt", EC_CERT)]);
+        let errors = super::data(Some(TLS), &data).unwrap_err();
+        assert_eq!(
+            vec![(None, "kubernetes.io/tls secrets require tls.key".to_string())],
+            errors
+        );
+    }
+
+    #[test]
+    fn tls_compacted_pem() {
+        let cert = EC_CERT.trim().replace('\n', r"\n");
+        let key = EC_KEY.trim().replace('\n', r"\n");
+        let data = BTreeMap::from([("tls.crt", cert.as_str()), ("tls.key", key.as_str())]);
+
+        let result = super::data(Some(TLS), &data).unwrap();
+        assert_eq!(EC_CERT.trim(), result["tls.crt"]);
+    }
+
+    #[test]
+    fn basic_auth() {
+        let data = BTreeMap::from([("username", "user")]);
+        assert!(super::data(Some(BASIC_AUTH), &data).is_ok());
+
+        let data = BTreeMap::from([("other", "user")]);
+        assert!(super::data(Some(BASIC_AUTH), &data).is_err());
+    }
+
+    #[test]
+    fn ssh_auth() {
+        let data = BTreeMap::from([("ssh-privatekey", EC_KEY)]);
+        assert!(super::data(Some(SSH_AUTH), &data).is_ok());
+
+        let data = BTreeMap::from([("ssh-privatekey", "nope")]);
+        assert!(super::data(Some(SSH_AUTH), &data).is_err());
+
+        assert!(super::data(Some(SSH_AUTH), &BTreeMap::new()).is_err());
+    }
+}
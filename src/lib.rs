@@ -1,21 +1,53 @@
 #![doc = include_str!("README.md")]
 
+mod check;
+mod config;
 mod crypto;
+mod diff;
+mod doctor;
+mod generate;
+mod inventory;
 mod json;
+mod krm;
 mod kube;
 mod map;
+mod materialize;
+mod merge;
+mod meta;
+mod verify;
 
 use std::path::Path;
 
 use anyhow::Result;
+use base64::Engine;
+pub use check::{CheckReport, Finding, Rule};
+pub use config::{CONFIG_FILE, Config, DEFAULT_ENV_KEY, DEFAULT_KUBE_SECRETS_KEY, PathRule};
 pub use crypto::{Key, KeyPair};
+pub use diff::{DiffEntry, SecretsDiff};
+pub use doctor::{DiagnosedFile, Diagnosis, Diagnostic, KeySource, diagnose, diagnose_with};
+pub use generate::{Charset, DEFAULT_LENGTH, DEFAULT_WORDS, Generator};
+pub use inventory::{Inventory, InventoryEntry, SecretStatus};
 pub use json::SecretsFile;
-pub use kube::SecretsManifest;
+pub use krm::{KrmConfig, ResourceList};
+pub use kube::{Change, DirSummary, KeyDiff, ManifestDiff, SOURCE_ANNOTATION, SecretDiff, SecretsManifest};
 pub use map::SecretsMap;
+pub use materialize::{MANIFEST_FILE, SecretsDir};
+pub use merge::{Merged, merge};
+pub use meta::Metadata;
+pub use verify::{Problem, Severity, Verification, verify};
 
 const NEW_LINE: &str = "\n";
 const CARRIAGE_RETURN: &str = "\r";
 
+/// Prefix for values holding base64 encoded binary data.
+pub(crate) const BASE64_PREFIX: &str = "base64:";
+
+/// Prefix for values replaced by [redact].
+const REDACTED_PREFIX: &str = "REDACTED[";
+
+/// The number of bytes of a hash to show when identifying a value without revealing it.
+const SHORT_HASH_LENGTH: usize = 4;
+
 /// Returns a transform function that compacts multiline strings into single lines with line
 /// break characters. This is useful when adding something like a service account in the EJSON file
 /// and having the encrypt function compact it before encryption.
@@ -36,7 +68,7 @@ pub fn compact() -> Result<impl Fn(String) -> Result<String>> {
 /// Returns a transform function for use with [SecretsFile::transform] that will encrypt all eligible
 /// values (that aren't already encrypted).
 pub fn encrypt(secrets_file: &SecretsFile) -> Result<impl Fn(String) -> Result<String> + use<>> {
-    let public_key = secrets_file.public_key().unwrap();
+    let public_key = public_key(secrets_file)?;
     let ephemeral_key = KeyPair::generate()?;
     let encryptor = ephemeral_key.encryptor(public_key)?;
 
@@ -54,7 +86,7 @@ pub fn encrypt(secrets_file: &SecretsFile) -> Result<impl Fn(String) -> Result<S
 /// done by creating a [KeyPair] consisting of the public key from the file and the supplied
 /// private key.
 pub fn decrypt(secrets_file: &SecretsFile, private_key: Key) -> Result<impl Fn(String) -> Result<String> + use<>> {
-    let public_key = secrets_file.public_key().unwrap();
+    let public_key = public_key(secrets_file)?;
     let decryptor = KeyPair::new(public_key, private_key).decryptor();
 
     Ok(move |s: String| {
@@ -67,13 +99,84 @@ pub fn decrypt(secrets_file: &SecretsFile, private_key: Key) -> Result<impl Fn(S
     })
 }
 
+/// Returns a transform like [decrypt], except plaintexts that aren't valid UTF-8 are returned base64
+/// encoded with a `base64:` prefix rather than failing. This allows binary values (keystores,
+/// `.p12` files, etc.) to be passed through verbatim to things like [SecretsManifest].
+pub fn decrypt_binary(
+    secrets_file: &SecretsFile,
+    private_key: Key,
+) -> Result<impl Fn(String) -> Result<String> + use<>> {
+    let public_key = public_key(secrets_file)?;
+    let decryptor = KeyPair::new(public_key, private_key).decryptor();
+    let b64 = base64::engine::general_purpose::STANDARD;
+
+    Ok(move |s: String| {
+        if !crypto::Message::is_valid(&s) {
+            // Skip decryption for values that aren't encrypted.
+            return Ok(s);
+        }
+
+        Ok(String::from_utf8(decryptor.decrypt_bytes(s)?)
+            .unwrap_or_else(|e| format!("{}{}", BASE64_PREFIX, b64.encode(e.into_bytes()))))
+    })
+}
+
+/// Returns a transform that replaces every encrypted value with a short hash of its ciphertext. This
+/// allows seeing which values differ between two versions of a file without a private key (values
+/// are only re-encrypted when they change).
+pub fn redact() -> Result<impl Fn(String) -> Result<String>> {
+    Ok(|s: String| {
+        if !crypto::Message::is_valid(&s) {
+            return Ok(s);
+        }
+
+        Ok(format!("{}{}]", REDACTED_PREFIX, short_hash(s.as_bytes())))
+    })
+}
+
 /// Loads the private key from disk, searching for a file named as the public key defined in the
 /// secrets file.
 pub fn load_private_key(secrets_file: &SecretsFile, keydir: &str) -> Result<Key> {
-    let public_key = secrets_file.public_key().unwrap();
+    let public_key = public_key(secrets_file)?;
     Key::from_file(Path::new(keydir).join(public_key.to_string()))
 }
 
+/// Returns the public key of the secrets file.
+fn public_key(secrets_file: &SecretsFile) -> Result<Key> {
+    secrets_file
+        .public_key()
+        .ok_or_else(|| anyhow::anyhow!("missing or invalid _public_key"))
+}
+
+/// Returns the first few bytes of the SHA-512 of the value, hex encoded.
+pub(crate) fn short_hash(value: &[u8]) -> String {
+    let mut hash = [0u8; 64];
+    nacl::sha512::hash_sha512(&mut hash, value);
+
+    hash[..SHORT_HASH_LENGTH].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the first few bytes of the HMAC-SHA512 of the value, hex encoded. The key must be no
+/// longer than the SHA-512 block size (128 bytes).
+pub(crate) fn keyed_hash(key: &[u8], value: &[u8]) -> String {
+    const BLOCK_SIZE: usize = 128;
+
+    let mut inner = [0x36u8; BLOCK_SIZE];
+    let mut outer = [0x5cu8; BLOCK_SIZE];
+    key.iter().enumerate().for_each(|(i, b)| {
+        inner[i] ^= b;
+        outer[i] ^= b;
+    });
+
+    let mut inner_hash = [0u8; 64];
+    nacl::sha512::hash_sha512(&mut inner_hash, &[&inner[..], value].concat());
+
+    let mut hash = [0u8; 64];
+    nacl::sha512::hash_sha512(&mut hash, &[&outer[..], &inner_hash[..]].concat());
+
+    hash[..SHORT_HASH_LENGTH].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,4 +196,47 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn decrypt_binary_transform() -> Result<()> {
+        let keys = KeyPair::generate()?;
+        let encryptor = KeyPair::generate()?.encryptor(keys.public.clone())?;
+        let secrets_file: SecretsFile = serde_json::json!({ "_public_key": keys.public_key() })
+            .to_string()
+            .parse()?;
+
+        let tf = decrypt_binary(&secrets_file, keys.private.clone())?;
+        assert_eq!("plain", tf(encryptor.encrypt("plain")?)?);
+        assert_eq!(
+            "base64:3q2+7w==",
+            tf(encryptor.encrypt_bytes([0xde, 0xad, 0xbe, 0xef])?)?
+        );
+        assert_eq!("unencrypted", tf("unencrypted".to_string())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn redact_transform() -> Result<()> {
+        let keys = KeyPair::generate()?;
+        let encryptor = KeyPair::generate()?.encryptor(keys.public.clone())?;
+        let encrypted = encryptor.encrypt("plain")?;
+
+        let tf = redact()?;
+        assert_eq!(
+            format!("REDACTED[{}]", short_hash(encrypted.as_bytes())),
+            tf(encrypted.clone())?
+        );
+        assert_eq!(tf(encrypted.clone())?, tf(encrypted)?);
+        assert_ne!(tf(encryptor.encrypt("plain")?)?, tf(encryptor.encrypt("other")?)?);
+        assert_eq!("unencrypted", tf("unencrypted".to_string())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keyed_hash_matches_hmac() {
+        // RFC 4231 test case 2.
+        assert_eq!("164b7a7b", keyed_hash(b"Jefe", b"what do ya want for nothing?"));
+    }
 }
@@ -74,6 +74,12 @@ impl Deref for SecretsMap {
     }
 }
 
+impl From<SecretsFile> for SecretsMap {
+    fn from(secrets: SecretsFile) -> Self {
+        secrets.value.into()
+    }
+}
+
 impl From<Value> for SecretsMap {
     fn from(value: Value) -> Self {
         let mut map = HashMap::new();
@@ -106,7 +112,7 @@ fn extract_keys(map: &mut HashMap<String, String>, key: &str, value: &Value) {
     }
 }
 
-fn safe_key<K: Into<String>>(k: K) -> String {
+pub(crate) fn safe_key<K: Into<String>>(k: K) -> String {
     let key = k.into();
 
     if key.contains(SEPARATOR) {
@@ -116,6 +122,46 @@ fn safe_key<K: Into<String>>(k: K) -> String {
     }
 }
 
+/// Splits a key in dot-notation (as used by [SecretsMap]) into its segments, unwrapping segments
+/// surrounded by square brackets.
+///
+/// ```text
+/// sub.key => [sub, key]
+/// sub.[file.ext] => [sub, file.ext]
+/// ```
+pub(crate) fn split_key(key: &str) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut rest = key;
+
+    while !rest.is_empty() {
+        let (segment, remainder) = split_first_segment(rest);
+        segments.push(
+            segment
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .unwrap_or(segment),
+        );
+        rest = remainder;
+    }
+
+    segments
+}
+
+/// Splits off the first segment of a key in dot-notation, keeping its square brackets (if any).
+///
+/// ```text
+/// sub.key => (sub, key)
+/// [file.ext].key => ([file.ext], key)
+/// ```
+pub(crate) fn split_first_segment(key: &str) -> (&str, &str) {
+    let end = match key.starts_with('[') {
+        true => key.find("].").map_or(key.len(), |i| i + 1),
+        false => key.find(SEPARATOR).unwrap_or(key.len()),
+    };
+
+    (&key[..end], key.get(end + 1..).unwrap_or_default())
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -201,4 +247,17 @@ mod tests {
         assert_eq!("n", map.fetch("environment._a.b"));
         assert_eq!("contents", map.fetch("environment._a.[key.json]"));
     }
+
+    #[test]
+    fn split_keys() {
+        assert_eq!(vec!["key"], split_key("key"));
+        assert_eq!(vec!["sub", "key"], split_key("sub.key"));
+        assert_eq!(vec!["sub", "file.ext", "key"], split_key("sub.[file.ext].key"));
+        assert_eq!(vec!["[file.ext]"], split_key("[[file.ext]]"));
+        assert!(split_key("").is_empty());
+
+        assert_eq!(("sub", "key.x"), split_first_segment("sub.key.x"));
+        assert_eq!(("[file.ext]", "key"), split_first_segment("[file.ext].key"));
+        assert_eq!(("key", ""), split_first_segment("key"));
+    }
 }
@@ -0,0 +1,419 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, DirBuilder, OpenOptions},
+    io::{ErrorKind, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use serde_json::{Map, Value};
+
+use crate::{BASE64_PREFIX, DirSummary, SecretsFile, map::split_key};
+
+const METADATA_PREFIX: &str = "_";
+const FILE_MODE: u32 = 0o400;
+const DIR_MODE: u32 = 0o700;
+const TEMP_SUFFIX: &str = ".rejson-tmp";
+
+/// File in the output directory listing the files written by rejson, one relative path per line.
+/// Only the files listed here are ever removed by [SecretsDir::with_clean].
+pub const MANIFEST_FILE: &str = ".rejson-manifest";
+
+/// Writes the (decrypted) values of a secrets file to a directory, one file per key, for daemons
+/// that read their secrets from files (Docker secrets, systemd credentials, `/run/secrets`, etc.).
+///
+/// Nested objects become subdirectories and keys starting with an underscore are skipped. Files are
+/// written atomically with mode `0400` and directories are created with mode `0700`. Values with the
+/// `base64:` prefix (see [crate::decrypt_binary]) are decoded before being written. The files that
+/// were written are recorded in [MANIFEST_FILE] in the output directory.
+#[derive(Debug)]
+pub struct SecretsDir<'a> {
+    value: &'a Map<String, Value>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    clean: bool,
+}
+
+impl<'a> SecretsDir<'a> {
+    /// Selects the values under the root key of the secrets file (in the dot-notation used by
+    /// [crate::SecretsMap]), or all of them when no root is supplied.
+    pub fn new(secrets_file: &'a SecretsFile, root: Option<&str>) -> Result<Self> {
+        let value = root
+            .into_iter()
+            .flat_map(split_key)
+            .try_fold(&secrets_file.value, |value, key| {
+                value.get(key).ok_or_else(|| anyhow!("{} not found", root.unwrap()))
+            })?
+            .as_object()
+            .ok_or_else(|| anyhow!("{} must be an object", root.unwrap_or("secrets file")))?;
+
+        Ok(Self {
+            value,
+            uid: None,
+            gid: None,
+            clean: false,
+        })
+    }
+
+    /// Sets the owner (uid) and/or group (gid) of every file and directory that's written.
+    pub fn with_owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    /// When enabled, files written by an earlier run (as recorded in [MANIFEST_FILE]) that no longer
+    /// correspond to a key are removed, along with any directories left empty. Files rejson didn't
+    /// write are never touched.
+    pub fn with_clean(mut self, enabled: bool) -> Self {
+        self.clean = enabled;
+        self
+    }
+
+    /// Writes the files to `dir`, creating it if necessary. Files whose contents and mode are already
+    /// up to date are not rewritten.
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> Result<DirSummary> {
+        let dir = dir.as_ref();
+        let mut files = BTreeMap::new();
+        let mut dirs = BTreeSet::new();
+        collect(self.value, Path::new(""), &mut files, &mut dirs)?;
+
+        let mut summary = DirSummary::default();
+
+        if !dir.exists() {
+            DirBuilder::new().recursive(true).mode(DIR_MODE).create(dir)?;
+            self.chown(dir)?;
+        }
+
+        dirs.iter().try_for_each(|path| self.create_dir(&dir.join(path)))?;
+
+        files.into_iter().try_for_each(|(path, contents)| -> Result<()> {
+            let full_path = dir.join(&path);
+
+            let up_to_date = fs::read(&full_path).is_ok_and(|existing| existing == contents)
+                && fs::metadata(&full_path).is_ok_and(|m| m.permissions().mode() & 0o777 == FILE_MODE);
+
+            match up_to_date {
+                true => {
+                    self.chown(&full_path)?;
+                    summary.unchanged.push(path);
+                }
+                false => {
+                    self.write_file(&full_path, &contents)
+                        .with_context(|| format!("writing {}", full_path.display()))?;
+                    summary.written.push(path);
+                }
+            }
+
+            Ok(())
+        })?;
+
+        let previous = read_manifest(dir)?;
+        let current = summary
+            .written
+            .iter()
+            .chain(&summary.unchanged)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let stale = previous.difference(&current).cloned().collect::<Vec<_>>();
+        let manifest = match self.clean {
+            true => {
+                remove_stale(dir, &stale, &dirs, &mut summary.removed)?;
+                current
+            }
+            // Keep track of the stale files so a later run with clean can still remove them.
+            false => previous.into_iter().chain(current).collect(),
+        };
+
+        self.write_manifest(dir, &manifest)?;
+        Ok(summary)
+    }
+
+    /// Writes the manifest of generated files, unless it's already up to date.
+    fn write_manifest(&self, dir: &Path, files: &BTreeSet<PathBuf>) -> Result<()> {
+        let contents = files
+            .iter()
+            .map(|path| format!("{}\n", path.display()))
+            .collect::<String>();
+
+        let path = dir.join(MANIFEST_FILE);
+        match fs::read_to_string(&path) {
+            Ok(existing) if existing == contents => Ok(()),
+            _ => self
+                .write_file(&path, contents.as_bytes())
+                .with_context(|| format!("writing {}", path.display())),
+        }
+    }
+
+    /// Creates the directory with the directory mode (or fixes the mode of an existing one).
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        match path.is_dir() {
+            true => fs::set_permissions(path, fs::Permissions::from_mode(DIR_MODE))?,
+            false => DirBuilder::new().mode(DIR_MODE).create(path)?,
+        }
+
+        self.chown(path)
+    }
+
+    /// Atomically replaces the file by writing to a temporary file in the same directory and
+    /// renaming it into place.
+    fn write_file(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(TEMP_SUFFIX);
+        let temp_path = path.with_file_name(temp_name);
+
+        // A previous run may have been interrupted before the rename.
+        let _ = fs::remove_file(&temp_path);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(FILE_MODE)
+            .open(&temp_path)?;
+
+        let result = file
+            .write_all(contents)
+            .and_then(|_| file.sync_all())
+            .map_err(anyhow::Error::from)
+            .and_then(|_| self.chown(&temp_path))
+            .and_then(|_| fs::rename(&temp_path, path).map_err(anyhow::Error::from));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        result
+    }
+
+    fn chown(&self, path: &Path) -> Result<()> {
+        if self.uid.is_some() || self.gid.is_some() {
+            std::os::unix::fs::chown(path, self.uid, self.gid)
+                .with_context(|| format!("changing the owner of {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the contents of every file (keyed by its relative path) and every directory under the
+/// supplied object.
+fn collect(
+    object: &Map<String, Value>,
+    parent: &Path,
+    files: &mut BTreeMap<PathBuf, Vec<u8>>,
+    dirs: &mut BTreeSet<PathBuf>,
+) -> Result<()> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+
+    object
+        .iter()
+        .filter(|(key, _)| !key.starts_with(METADATA_PREFIX))
+        .try_for_each(|(key, value)| {
+            let path = parent.join(key);
+            if !is_file_name(key) {
+                return Err(anyhow!("{}: key is not a valid file name", path.display()));
+            }
+
+            let contents = match value {
+                Value::Object(object) => {
+                    dirs.insert(path.clone());
+                    return collect(object, &path, files, dirs);
+                }
+                Value::String(s) => match s.strip_prefix(BASE64_PREFIX) {
+                    Some(encoded) => b64
+                        .decode(encoded)
+                        .with_context(|| format!("{}: invalid base64 value", path.display()))?,
+                    None => s.as_bytes().to_vec(),
+                },
+                Value::Null => vec![],
+                other => other.to_string().into_bytes(),
+            };
+
+            files.insert(path, contents);
+            Ok(())
+        })
+}
+
+/// Returns the files listed in the manifest of the directory (empty when there isn't one).
+fn read_manifest(dir: &Path) -> Result<BTreeSet<PathBuf>> {
+    match fs::read_to_string(dir.join(MANIFEST_FILE)) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .filter(|path| path.components().all(|c| matches!(c, Component::Normal(_))))
+            .collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeSet::new()),
+        Err(e) => Err(e).with_context(|| format!("reading {}", dir.join(MANIFEST_FILE).display())),
+    }
+}
+
+/// Removes the stale files (relative to `root`) and then any of their parent directories that are
+/// left empty and aren't in `keep`, recording the removed paths.
+fn remove_stale(root: &Path, stale: &[PathBuf], keep: &BTreeSet<PathBuf>, removed: &mut Vec<PathBuf>) -> Result<()> {
+    let mut parents = BTreeSet::new();
+
+    stale.iter().try_for_each(|path| -> Result<()> {
+        let full_path = root.join(path);
+        if full_path.is_file() {
+            fs::remove_file(&full_path)?;
+            removed.push(path.clone());
+        }
+
+        parents.extend(
+            path.ancestors()
+                .skip(1)
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(Path::to_path_buf),
+        );
+        Ok(())
+    })?;
+
+    // Deepest first, so nested directories are removed before their parents.
+    parents
+        .into_iter()
+        .rev()
+        .filter(|path| !keep.contains(path))
+        .try_for_each(|path| -> Result<()> {
+            let full_path = root.join(&path);
+            if full_path.is_dir() && fs::read_dir(&full_path)?.next().is_none() {
+                fs::remove_dir(&full_path)?;
+                removed.push(path);
+            }
+
+            Ok(())
+        })
+}
+
+/// Returns whether the key can be used as a single file name.
+fn is_file_name(key: &str) -> bool {
+    !matches!(key, "" | "." | ".." | MANIFEST_FILE) && !key.contains(['/', '\0', '\n']) && !key.ends_with(TEMP_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn secrets_file(value: Value) -> SecretsFile {
+        SecretsFile { value }
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn write() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let out = dir.path().join("secrets");
+        let file = secrets_file(json!({
+            "_public_key": "abc",
+            "environment": {
+                "_comment": "skipped",
+                "API_KEY": "key",
+                "db": { "password": "pw", "port": 5432 },
+                "keystore": "base64:3q2+7w=="
+            }
+        }));
+
+        let summary = SecretsDir::new(&file, Some("environment"))?.write(&out)?;
+        assert_eq!(
+            vec![
+                PathBuf::from("API_KEY"),
+                PathBuf::from("db/password"),
+                PathBuf::from("db/port"),
+                PathBuf::from("keystore")
+            ],
+            summary.written
+        );
+
+        assert_eq!("key", fs::read_to_string(out.join("API_KEY"))?);
+        assert_eq!("5432", fs::read_to_string(out.join("db/port"))?);
+        assert_eq!(vec![0xde, 0xad, 0xbe, 0xef], fs::read(out.join("keystore"))?);
+        assert!(!out.join("_comment").exists());
+
+        assert_eq!(FILE_MODE, mode(&out.join("API_KEY")));
+        assert_eq!(DIR_MODE, mode(&out));
+        assert_eq!(DIR_MODE, mode(&out.join("db")));
+
+        let summary = SecretsDir::new(&file, Some("environment"))?.write(&out)?;
+        assert!(summary.written.is_empty());
+        assert_eq!(4, summary.unchanged.len());
+
+        let file = secrets_file(json!({ "app.example.com": { "tls": { "key": "pem" } } }));
+        SecretsDir::new(&file, Some("[app.example.com].tls"))?.write(&out)?;
+        assert_eq!("pem", fs::read_to_string(out.join("key"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_clean() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        let file = secrets_file(json!({ "a": "1", "nested": { "b": "2" }, "old": { "c": "3" } }));
+        SecretsDir::new(&file, None)?.write(dir.path())?;
+
+        let file = secrets_file(json!({ "a": "1", "nested": {} }));
+        let summary = SecretsDir::new(&file, None)?.write(dir.path())?;
+        assert!(summary.removed.is_empty(), "only removed with clean");
+
+        let summary = SecretsDir::new(&file, None)?.with_clean(true).write(dir.path())?;
+        assert_eq!(
+            vec![PathBuf::from("nested/b"), PathBuf::from("old/c"), PathBuf::from("old")],
+            summary.removed
+        );
+        assert!(dir.path().join("nested").is_dir());
+        assert!(!dir.path().join("old").exists());
+        assert_eq!("a\n", fs::read_to_string(dir.path().join(MANIFEST_FILE))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_clean_keeps_unknown_files() -> Result<()> {
+        let dir = assert_fs::TempDir::new()?;
+        fs::create_dir(dir.path().join("data"))?;
+        fs::write(dir.path().join("data/keep"), "not ours")?;
+        fs::write(dir.path().join("unrelated"), "not ours")?;
+
+        let file = secrets_file(json!({ "a": "1", "data": { "b": "2" } }));
+        SecretsDir::new(&file, None)?.with_clean(true).write(dir.path())?;
+
+        let file = secrets_file(json!({ "a": "1" }));
+        let summary = SecretsDir::new(&file, None)?.with_clean(true).write(dir.path())?;
+        assert_eq!(vec![PathBuf::from("data/b")], summary.removed);
+        assert_eq!("not ours", fs::read_to_string(dir.path().join("data/keep"))?);
+        assert!(dir.path().join("unrelated").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let file = secrets_file(
+            json!({ "environment": "nope", "bad": { "..": "x" }, "manifest": { ".rejson-manifest": "x" } }),
+        );
+
+        assert!(SecretsDir::new(&file, Some("missing")).is_err());
+        assert!(SecretsDir::new(&file, Some("environment")).is_err());
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        let err = SecretsDir::new(&file, Some("bad"))
+            .unwrap()
+            .write(dir.path())
+            .unwrap_err();
+        assert_eq!("..: key is not a valid file name", err.to_string());
+
+        assert!(
+            SecretsDir::new(&file, Some("manifest"))
+                .unwrap()
+                .write(dir.path())
+                .is_err()
+        );
+    }
+}
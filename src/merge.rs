@@ -0,0 +1,185 @@
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+
+use crate::{SecretsFile, map::safe_key};
+
+/// The result of a three-way merge of secrets files.
+#[derive(Debug)]
+pub struct Merged {
+    /// The merged file. Conflicting values are taken from our side.
+    pub file: SecretsFile,
+    /// The (dot-delimited) paths of the values that were changed differently on both sides.
+    pub conflicts: Vec<String>,
+}
+
+/// Performs a structural three-way merge of two secrets files (`ours` and `theirs`) that were both
+/// changed from `base`.
+///
+/// Keys added, changed or removed on only one side are merged cleanly. When a value was changed on
+/// both sides, the sides are compared using the supplied `decrypt` transform (if any), so values that
+/// were re-encrypted to the same plaintext don't conflict. Anything else is reported as a conflict.
+pub fn merge<F: Fn(String) -> Result<String>>(
+    base: &SecretsFile,
+    ours: &SecretsFile,
+    theirs: &SecretsFile,
+    decrypt: Option<F>,
+) -> Result<Merged> {
+    if !ours.value.is_object() || !theirs.value.is_object() {
+        return Err(anyhow!("secrets files must be JSON objects"));
+    }
+
+    let merger = Merger { decrypt };
+    let mut conflicts = vec![];
+    let value = merger
+        .merge(
+            None,
+            Some(&base.value),
+            Some(&ours.value),
+            Some(&theirs.value),
+            &mut conflicts,
+        )
+        .unwrap_or_else(|| Value::Object(Map::new()));
+
+    Ok(Merged {
+        file: SecretsFile { value },
+        conflicts,
+    })
+}
+
+struct Merger<F> {
+    decrypt: Option<F>,
+}
+
+impl<F: Fn(String) -> Result<String>> Merger<F> {
+    /// Merges the values at the path, returning `None` when the value was removed.
+    fn merge(
+        &self,
+        path: Option<&str>,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+        conflicts: &mut Vec<String>,
+    ) -> Option<Value> {
+        if self.same(ours, theirs) {
+            return ours.cloned();
+        }
+
+        if self.same(base, ours) {
+            return theirs.cloned();
+        }
+
+        if self.same(base, theirs) {
+            return ours.cloned();
+        }
+
+        if let (Some(Value::Object(ours)), Some(Value::Object(theirs))) = (ours, theirs) {
+            let base = base.and_then(Value::as_object);
+            let merged = ours
+                .keys()
+                .chain(theirs.keys().filter(|k| !ours.contains_key(*k)))
+                .filter_map(|key| {
+                    let child = match path {
+                        Some(path) => format!("{}.{}", path, safe_key(key.as_str())),
+                        None => safe_key(key.as_str()),
+                    };
+
+                    self.merge(
+                        Some(&child),
+                        base.and_then(|b| b.get(key)),
+                        ours.get(key),
+                        theirs.get(key),
+                        conflicts,
+                    )
+                    .map(|value| (key.to_string(), value))
+                })
+                .collect();
+
+            return Some(Value::Object(merged));
+        }
+
+        conflicts.push(path.unwrap_or_default().to_string());
+        ours.cloned()
+    }
+
+    /// Returns whether the values are the same, comparing the plaintext of encrypted values when
+    /// they can be decrypted.
+    fn same(&self, a: Option<&Value>, b: Option<&Value>) -> bool {
+        match (a, b) {
+            (None, None) => true,
+            (Some(Value::String(a)), Some(Value::String(b))) => {
+                a == b
+                    || self
+                        .decrypt
+                        .as_ref()
+                        .and_then(|decrypt| Some(decrypt(a.to_string()).ok()? == decrypt(b.to_string()).ok()?))
+                        .unwrap_or_default()
+            }
+            (Some(Value::Object(a)), Some(Value::Object(b))) => {
+                a.len() == b.len() && a.iter().all(|(k, v)| self.same(Some(v), b.get(k)))
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn file(value: Value) -> SecretsFile {
+        SecretsFile { value }
+    }
+
+    fn no_decrypt() -> Option<fn(String) -> Result<String>> {
+        None
+    }
+
+    #[test]
+    fn merge_independent_changes() -> Result<()> {
+        let base = file(json!({ "_public_key": "pk", "a": "1", "b": "2", "nested": { "c": "3" } }));
+        let ours = file(json!({ "_public_key": "pk", "a": "changed", "b": "2", "nested": { "c": "3", "d": "4" } }));
+        let theirs = file(json!({ "_public_key": "pk", "a": "1", "nested": { "c": "3", "e": "5" }, "f": "6" }));
+
+        let merged = merge(&base, &ours, &theirs, no_decrypt())?;
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(
+            json!({ "_public_key": "pk", "a": "changed", "nested": { "c": "3", "d": "4", "e": "5" }, "f": "6" })
+                .to_string(),
+            merged.file.value.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_conflicts() -> Result<()> {
+        let base = file(json!({ "a": "1", "b": "2", "c": { "file.ext": "3" } }));
+        let ours = file(json!({ "a": "ours", "c": { "file.ext": "ours" } }));
+        let theirs = file(json!({ "a": "theirs", "b": "changed", "c": { "file.ext": "theirs" } }));
+
+        let merged = merge(&base, &ours, &theirs, no_decrypt())?;
+        assert_eq!(vec!["a", "c.[file.ext]", "b"], merged.conflicts);
+        assert_eq!("ours", merged.file.value["a"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_same_plaintext() -> Result<()> {
+        let base = file(json!({ "a": "1" }));
+        let ours = file(json!({ "a": "x:2" }));
+        let theirs = file(json!({ "a": "y:2" }));
+
+        // Stand in for decryption by stripping the "ciphertext" prefix.
+        let decrypt = |s: String| Ok(s.split_once(':').map_or(s.clone(), |(_, v)| v.to_string()));
+
+        let merged = merge(&base, &ours, &theirs, Some(decrypt))?;
+        assert!(merged.conflicts.is_empty());
+        assert_eq!("x:2", merged.file.value["a"]);
+
+        assert_eq!(vec!["a"], merge(&base, &ours, &theirs, no_decrypt())?.conflicts);
+        Ok(())
+    }
+}
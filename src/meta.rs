@@ -0,0 +1,152 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use time::{Date, OffsetDateTime, format_description::well_known::Rfc3339, macros::format_description};
+
+/// The key of the objects holding the metadata of their sibling values. They're never encrypted.
+pub(crate) const META_KEY: &str = "_meta";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The metadata of a single value, read from the `_meta` object next to it.
+///
+/// ```json
+/// {
+///   "database": {
+///     "password": "EJ[...]",
+///     "_meta": {
+///       "password": {
+///         "description": "The primary database password",
+///         "owner": "team-db",
+///         "rotated_at": "2024-01-01T00:00:00Z",
+///         "expires_at": "2024-07-01"
+///       }
+///     }
+///   }
+/// }
+/// ```
+///
+/// Timestamps are RFC 3339 (or just a date, meaning midnight UTC). `rotated_at` is updated
+/// automatically when a value is changed by rejson.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// The (dot-delimited) key of the value.
+    pub key: String,
+    pub description: Option<String>,
+    pub owner: Option<String>,
+    pub rotated_at: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+impl Metadata {
+    /// Reads the metadata for the key from its entry in a `_meta` object.
+    pub(crate) fn from_value(key: String, value: &Value) -> Self {
+        let field = |name: &str| value.get(name).and_then(Value::as_str).map(str::to_string);
+
+        Self {
+            key,
+            description: field("description"),
+            owner: field("owner"),
+            rotated_at: field("rotated_at"),
+            expires_at: field("expires_at"),
+        }
+    }
+
+    /// Returns whether the value expires before `now + within` (including values that have already
+    /// expired). Values without an `expires_at` never expire.
+    pub fn expires_within(&self, within: Duration, now: SystemTime) -> Result<bool> {
+        Ok(self
+            .expires()?
+            .is_some_and(|expires| now.checked_add(within).is_none_or(|limit| expires <= limit)))
+    }
+
+    /// Returns the parsed `expires_at`, if any.
+    pub fn expires(&self) -> Result<Option<SystemTime>> {
+        self.expires_at
+            .as_deref()
+            .map(|s| parse_timestamp(s).with_context(|| format!("{}: invalid expires_at {}", self.key, s)))
+            .transpose()
+    }
+
+    /// Returns a short description of when the value expires relative to `now`, e.g. "in 3 days".
+    pub fn expires_in(&self, now: SystemTime) -> Result<Option<String>> {
+        Ok(self.expires()?.map(|expires| match expires.duration_since(now) {
+            Ok(left) => format!("in {} days", left.as_secs() / SECONDS_PER_DAY),
+            Err(e) => format!("{} days ago", e.duration().as_secs() / SECONDS_PER_DAY),
+        }))
+    }
+
+    /// Returns the metadata as a JSON object.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "key": self.key,
+            "description": self.description,
+            "owner": self.owner,
+            "rotated_at": self.rotated_at,
+            "expires_at": self.expires_at,
+        })
+    }
+}
+
+/// Formats the time as an RFC 3339 timestamp (in UTC, to the second).
+pub(crate) fn timestamp(at: SystemTime) -> Result<String> {
+    let at = OffsetDateTime::from(at).replace_nanosecond(0)?;
+    Ok(at.format(&Rfc3339)?)
+}
+
+/// Parses an RFC 3339 timestamp or a date (as midnight UTC).
+fn parse_timestamp(s: &str) -> Result<SystemTime> {
+    if let Ok(at) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(at.into());
+    }
+
+    let date = Date::parse(s, format_description!("[year]-[month]-[day]"))
+        .map_err(|_| anyhow!("expected an RFC 3339 timestamp or a YYYY-MM-DD date"))?;
+    Ok(date.midnight().assume_utc().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> SystemTime {
+        parse_timestamp(s).unwrap()
+    }
+
+    #[test]
+    fn timestamps() -> Result<()> {
+        assert_eq!("2024-02-29T12:30:00Z", timestamp(at("2024-02-29T12:30:00.5Z"))?);
+        assert_eq!("2024-02-29T00:00:00Z", timestamp(at("2024-02-29"))?);
+        assert_eq!("2024-02-29T10:30:00Z", timestamp(at("2024-02-29T12:30:00+02:00"))?);
+        assert!(parse_timestamp("next week").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn expires() -> Result<()> {
+        let now = at("2024-01-01T00:00:00Z");
+        let days = |n| Duration::from_secs(n * SECONDS_PER_DAY);
+        let meta = |expires_at: Option<&str>| Metadata {
+            key: "password".into(),
+            expires_at: expires_at.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert!(meta(Some("2024-01-31")).expires_within(days(30), now)?);
+        assert!(!meta(Some("2024-02-01")).expires_within(days(30), now)?);
+        assert!(meta(Some("2023-12-01")).expires_within(days(0), now)?);
+        assert!(!meta(None).expires_within(days(365), now)?);
+        assert!(meta(Some("2024-02-01")).expires_within(Duration::MAX, now)?);
+
+        assert_eq!(Some("in 31 days".into()), meta(Some("2024-02-01")).expires_in(now)?);
+        assert_eq!(Some("31 days ago".into()), meta(Some("2023-12-01")).expires_in(now)?);
+        assert_eq!(
+            "password: invalid expires_at soon",
+            meta(Some("soon")).expires().unwrap_err().to_string()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+
+use crate::{
+    Key,
+    KeyPair,
+    SecretsFile,
+    crypto::{Decryptor, Message},
+    json::Entry,
+};
+
+/// The result of attempting to decrypt every encrypted value in a secrets file. Plaintexts are
+/// never kept.
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// The number of values that were decrypted successfully.
+    pub verified: usize,
+    /// The problems found, in the order of the values in the file.
+    pub problems: Vec<Problem>,
+}
+
+/// A problem with a single value.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// The (dot-delimited) path of the value.
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// How serious a [Problem] (or a [crate::Diagnostic]) is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The value can't be decrypted (or the key can't be used).
+    Error,
+    /// The value can be decrypted (or the key used), but is suspicious.
+    Warning,
+}
+
+impl Verification {
+    /// Returns whether every value could be decrypted (warnings are allowed).
+    pub fn is_ok(&self) -> bool {
+        self.problems.iter().all(|p| p.severity == Severity::Warning)
+    }
+
+    /// Returns the verification as JSON.
+    ///
+    /// ```json
+    /// {
+    ///   "verified": 2,
+    ///   "problems": [{ "path": "database.password", "severity": "error", "message": "..." }]
+    /// }
+    /// ```
+    pub fn to_json(&self) -> Value {
+        let problems = self
+            .problems
+            .iter()
+            .map(|p| json!({ "path": p.path, "severity": p.severity.as_str(), "message": p.message }))
+            .collect::<Vec<_>>();
+
+        json!({ "verified": self.verified, "problems": problems })
+    }
+}
+
+impl Severity {
+    /// Returns the name used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+impl fmt::Display for Verification {
+    /// Writes a line per problem.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.problems.iter().try_for_each(|problem| {
+            writeln!(
+                f,
+                "{}: {}: {}",
+                problem.severity.as_str(),
+                problem.path,
+                problem.message
+            )
+        })
+    }
+}
+
+/// Attempts to decrypt every encrypted value in the secrets file with the supplied private key,
+/// reporting the path of each value that fails rather than stopping at the first one.
+///
+/// Values whose plaintext isn't valid UTF-8, and values encrypted with a different ephemeral key than
+/// most of the file (which can indicate they were encrypted elsewhere, e.g. to an old public key),
+/// are reported as warnings.
+pub fn verify(secrets_file: &SecretsFile, private_key: Key) -> Result<Verification> {
+    let public_key = secrets_file
+        .public_key()
+        .ok_or_else(|| anyhow!("missing or invalid _public_key"))?;
+    let decryptor = KeyPair::new(public_key, private_key).decryptor();
+
+    let mut messages = vec![];
+    let mut verification = Verification::default();
+    secrets_file.visit(false, |entry| collect(entry, &mut messages, &mut verification));
+
+    // The ephemeral key used for most of the values (or the first one on a tie) is assumed to be
+    // the expected one.
+    let counts = messages.iter().fold(HashMap::new(), |mut counts, (_, _, message)| {
+        *counts.entry(message.key.0).or_insert(0) += 1;
+        counts
+    });
+    let common_key = messages
+        .iter()
+        .map(|(_, _, message)| message.key.0)
+        .rev()
+        .max_by_key(|key| counts[key]);
+
+    messages.iter().for_each(|(path, ciphertext, message)| {
+        verify_message(&decryptor, path, ciphertext, &mut verification);
+
+        if common_key.is_some_and(|key| key != message.key.0) {
+            verification.problems.push(Problem {
+                path: path.to_string(),
+                severity: Severity::Warning,
+                message: "encrypted with a different ephemeral key than the rest of the file".into(),
+            });
+        }
+    });
+
+    // Keep problems in the order of the values in the file.
+    let order = messages
+        .iter()
+        .enumerate()
+        .map(|(i, (path, _, _))| (path.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    verification
+        .problems
+        .sort_by_key(|p| order.get(p.path.as_str()).copied().unwrap_or(usize::MAX));
+
+    Ok(verification)
+}
+
+/// Collects the encrypted values, recording values that look encrypted but can't be parsed.
+fn collect(entry: Entry, messages: &mut Vec<(String, String, Message)>, verification: &mut Verification) {
+    let Some(s) = entry
+        .value
+        .as_str()
+        .filter(|s| entry.is_eligible() && Message::is_valid(s))
+    else {
+        return;
+    };
+
+    match s.parse() {
+        Ok(message) => messages.push((entry.path, s.to_string(), message)),
+        Err(_) => verification.problems.push(Problem {
+            path: entry.path,
+            severity: Severity::Error,
+            message: "malformed encrypted value".into(),
+        }),
+    }
+}
+
+fn verify_message(decryptor: &Decryptor, path: &str, ciphertext: &str, verification: &mut Verification) {
+    match decryptor.decrypt_bytes(ciphertext) {
+        Ok(plaintext) => {
+            verification.verified += 1;
+
+            if std::str::from_utf8(&plaintext).is_err() {
+                verification.problems.push(Problem {
+                    path: path.to_string(),
+                    severity: Severity::Warning,
+                    message: "decrypted value is not valid UTF-8".into(),
+                });
+            }
+        }
+        Err(_) => verification.problems.push(Problem {
+            path: path.to_string(),
+            severity: Severity::Error,
+            message: "failed to decrypt (was it encrypted with a different public key?)".into(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn verify_values() -> Result<()> {
+        let keys = KeyPair::generate()?;
+        let encryptor = KeyPair::generate()?.encryptor(keys.public.clone())?;
+        let other = KeyPair::generate()?.encryptor(keys.public.clone())?;
+        let wrong = KeyPair::generate()?.encryptor(KeyPair::generate()?.public)?;
+
+        let secrets_file = SecretsFile {
+            value: json!({
+                "_public_key": keys.public_key(),
+                "a": encryptor.encrypt("a")?,
+                "b": encryptor.encrypt("b")?,
+                "nested": {
+                    "binary": encryptor.encrypt_bytes([0xff, 0xfe])?,
+                    "other": other.encrypt("c")?,
+                    "wrong": wrong.encrypt("d")?,
+                },
+                "plain": "not encrypted",
+                "_skipped": wrong.encrypt("e")?,
+                "_meta": { "a": { "note": wrong.encrypt("f")? } },
+            }),
+        };
+
+        let verification = verify(&secrets_file, keys.private.clone())?;
+        assert!(!verification.is_ok());
+        assert_eq!(4, verification.verified);
+        assert_eq!(
+            "warning: nested.binary: decrypted value is not valid UTF-8\n\
+             warning: nested.other: encrypted with a different ephemeral key than the rest of the file\n\
+             error: nested.wrong: failed to decrypt (was it encrypted with a different public key?)\n\
+             warning: nested.wrong: encrypted with a different ephemeral key than the rest of the file\n",
+            verification.to_string()
+        );
+
+        let json = verification.to_json();
+        assert_eq!(4, json["verified"]);
+        assert_eq!(
+            json!({ "path": "nested.binary", "severity": "warning", "message": "decrypted value is not valid UTF-8" }),
+            json["problems"][0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_ok() -> Result<()> {
+        let keys = KeyPair::generate()?;
+        let encryptor = KeyPair::generate()?.encryptor(keys.public.clone())?;
+        let secrets_file = SecretsFile {
+            value: json!({ "_public_key": keys.public_key(), "a": encryptor.encrypt("a")? }),
+        };
+
+        let verification = verify(&secrets_file, keys.private.clone())?;
+        assert!(verification.is_ok());
+        assert!(verification.problems.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn check() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    fs::create_dir_all(dir.path().join("config/.hidden"))?;
+    fs::write(
+        dir.path().join("config/good.ejson"),
+        serde_json::json!({ "_public_key": PUB_KEY, "password": ENCRYPTED }).to_string(),
+    )?;
+    fs::write(dir.path().join("config/.hidden/bad.ejson"), "{}")?;
+    fs::write(dir.path().join("config/other.json"), "{}")?;
+
+    cargo_bin_cmd!()
+        .arg("check")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("Checked 1 file(s)\n");
+
+    fs::write(
+        dir.path().join("config/bad.ejson"),
+        serde_json::json!({ "_public_key": PUB_KEY, "password": "hunter2" }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("check")
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains(
+            "bad.ejson: password: value is not encrypted [plaintext-value]",
+        ))
+        .stderr(predicates::str::contains("Found 1 problem(s)"));
+
+    Ok(())
+}
+
+#[test]
+fn check_formats() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(file.path(), serde_json::json!({ "password": "hunter2" }).to_string())?;
+
+    let output = cargo_bin_cmd!()
+        .arg("check")
+        .arg(file.path())
+        .args(["--format", "json"])
+        .assert()
+        .failure()
+        .get_output()
+        .stdout
+        .clone();
+
+    let json: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!("public-key", json["findings"][0]["rule"]);
+    assert_eq!("password", json["findings"][1]["path"]);
+
+    let output = cargo_bin_cmd!()
+        .arg("check")
+        .arg(file.path())
+        .args(["--format", "sarif"])
+        .assert()
+        .failure()
+        .get_output()
+        .stdout
+        .clone();
+
+    let sarif: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!("2.1.0", sarif["version"]);
+    assert_eq!(2, sarif["runs"][0]["results"].as_array().unwrap().len());
+
+    Ok(())
+}
+
+#[test]
+fn check_fail_fast() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let plaintext = serde_json::json!({ "_public_key": PUB_KEY, "password": "hunter2" }).to_string();
+    fs::write(dir.path().join("a.ejson"), &plaintext)?;
+    fs::write(dir.path().join("b.ejson"), &plaintext)?;
+    fs::write(dir.path().join("c.secrets"), &plaintext)?;
+
+    cargo_bin_cmd!()
+        .arg("check")
+        .arg(dir.path())
+        .arg("--fail-fast")
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("a.ejson: password").and(predicates::str::contains("b.ejson").not()));
+
+    cargo_bin_cmd!()
+        .arg("check")
+        .arg(dir.path())
+        .args(["--include", "*.secrets", "--include", "b.*"])
+        .assert()
+        .failure()
+        .stdout(
+            predicates::str::contains("c.secrets: password")
+                .and(predicates::str::contains("b.ejson: password"))
+                .and(predicates::str::contains("a.ejson").not()),
+        );
+
+    Ok(())
+}
@@ -0,0 +1,233 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+use serde_json::{Value, json};
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+const CONFIG: &str = r#"
+keydir = "keys"
+env_key = "env"
+output = "human"
+
+[formats]
+check = "json"
+
+[[rules]]
+path = "payments/**"
+keydir = "payments/keys"
+"#;
+
+/// Creates a project with a config, a keydir (for the payments directory) and a secrets file in a subdirectory.
+fn project() -> Result<assert_fs::TempDir> {
+    let dir = assert_fs::TempDir::new()?;
+    fs::write(dir.path().join(".rejson.toml"), CONFIG)?;
+    fs::create_dir_all(dir.path().join("api"))?;
+    fs::create_dir_all(dir.path().join("payments/keys"))?;
+    fs::write(dir.path().join("payments/keys").join(PUB_KEY), PRIV_KEY)?;
+
+    let file = json!({ "_public_key": PUB_KEY, "env": { "PASSWORD": ENCRYPTED } }).to_string();
+    fs::write(dir.path().join("api/secrets.ejson"), &file)?;
+    fs::write(dir.path().join("payments/secrets.ejson"), &file)?;
+    Ok(dir)
+}
+
+#[test]
+fn config_show() -> Result<()> {
+    let dir = project()?;
+    let keydir = dir.path().join("keys").display().to_string();
+
+    cargo_bin_cmd!()
+        .args(["config", "show"])
+        .current_dir(dir.path().join("api"))
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "# {}",
+            dir.path().join(".rejson.toml").display()
+        )))
+        .stdout(predicates::str::contains(format!("keydir = \"{}\"", keydir)))
+        .stdout(predicates::str::contains("env_key = \"env\""))
+        .stdout(predicates::str::contains("check = \"json\""))
+        .stdout(predicates::str::contains("inventory = \"markdown\""));
+
+    // Flags override the config.
+    let output = cargo_bin_cmd!()
+        .args(["config", "show", "--keydir", "/elsewhere", "--output", "json"])
+        .current_dir(dir.path())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let report: Value = serde_json::from_slice(&output)?;
+    assert_eq!("/elsewhere", report["result"]["keydir"]);
+    assert_eq!("json", report["result"]["output"]);
+    assert_eq!(
+        dir.path().join("payments/keys").display().to_string(),
+        report["result"]["rules"][0]["keydir"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn config_defaults() -> Result<()> {
+    let dir = project()?;
+
+    // The keydir of the matching rule is used.
+    cargo_bin_cmd!()
+        .args(["env", "payments/secrets.ejson"])
+        .current_dir(dir.path())
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .success()
+        .stdout("export PASSWORD=test\n");
+
+    // Files without a rule use the configured keydir, which doesn't have the key.
+    cargo_bin_cmd!()
+        .args(["decrypt", "secrets.ejson"])
+        .current_dir(dir.path().join("api"))
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .code(3)
+        .stderr(predicates::str::contains("missing private key"));
+
+    // The configured format is used unless one is supplied.
+    cargo_bin_cmd!()
+        .args(["check", "."])
+        .current_dir(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\"findings\": []"));
+    cargo_bin_cmd!()
+        .args(["check", ".", "--format", "human"])
+        .current_dir(dir.path())
+        .assert()
+        .success()
+        .stdout("Checked 2 file(s)\n");
+
+    Ok(())
+}
+
+#[test]
+fn config_key_env() -> Result<()> {
+    let dir = project()?;
+    fs::write(
+        dir.path().join(".rejson.toml"),
+        format!("key_env = \"REJSON_TEST_KEY\"\n{}", CONFIG),
+    )?;
+
+    // The key_env setting is used instead of the keydir setting...
+    cargo_bin_cmd!()
+        .args(["env", "api/secrets.ejson"])
+        .current_dir(dir.path())
+        .env("REJSON_TEST_KEY", PRIV_KEY)
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .success()
+        .stdout("export PASSWORD=test\n");
+
+    // ...but not the keydir of a matching rule, which is more specific.
+    cargo_bin_cmd!()
+        .args(["env", "payments/secrets.ejson"])
+        .current_dir(dir.path())
+        .env("REJSON_TEST_KEY", "not a key")
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .success()
+        .stdout("export PASSWORD=test\n");
+
+    Ok(())
+}
+
+#[test]
+fn config_doctor() -> Result<()> {
+    let dir = project()?;
+    fs::write(
+        dir.path().join(".rejson.toml"),
+        format!("key_env = \"REJSON_TEST_KEY\"\n{}", CONFIG),
+    )?;
+    fs::create_dir(dir.path().join("keys"))?;
+
+    // Files use the keydir of their rule, or the key in key_env.
+    cargo_bin_cmd!()
+        .args(["doctor", "."])
+        .current_dir(dir.path())
+        .env("REJSON_TEST_KEY", PRIV_KEY)
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("ok: ./api/secrets.ejson"))
+        .stdout(predicates::str::contains("ok: ./payments/secrets.ejson"))
+        .stdout(predicates::str::contains("EJSON_KEYDIR isn't set").not());
+
+    cargo_bin_cmd!()
+        .args(["doctor", "."])
+        .current_dir(dir.path())
+        .env("REJSON_TEST_KEY", "not a key")
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .code(5)
+        .stdout(predicates::str::contains("missing: ./api/secrets.ejson"))
+        .stdout(predicates::str::contains("set REJSON_TEST_KEY to the private key"))
+        .stdout(predicates::str::contains("ok: ./payments/secrets.ejson"));
+
+    Ok(())
+}
+
+#[test]
+fn config_kube_secrets_key() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    fs::write(dir.path().join(".rejson.toml"), "kube_secrets_key = \"k8s\"")?;
+    fs::write(
+        dir.path().join("secrets.ejson"),
+        json!({
+            "_public_key": PUB_KEY,
+            "k8s": { "app": { "PASSWORD": ENCRYPTED } },
+            "k8s_note": "keys sharing the prefix aren't secrets"
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .args(["kube-secrets", "secrets.ejson", "--key-from-stdin", "--string-data"])
+        .current_dir(dir.path())
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("name: app\n"))
+        .stdout(predicates::str::contains("PASSWORD: test\n"));
+
+    Ok(())
+}
+
+#[test]
+fn config_invalid() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    fs::write(dir.path().join(".rejson.toml"), "kedir = \"keys\"")?;
+
+    cargo_bin_cmd!()
+        .args(["config", "show"])
+        .current_dir(dir.path())
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("unknown setting kedir"));
+
+    fs::write(dir.path().join(".rejson.toml"), "output = \"yaml\"")?;
+    cargo_bin_cmd!()
+        .args(["config", "show"])
+        .current_dir(dir.path())
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("invalid output in .rejson.toml"));
+
+    Ok(())
+}
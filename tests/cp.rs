@@ -0,0 +1,82 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+// The public key of the private key in examples/data.
+const OTHER_PUB_KEY: &str = "2549b26efec29cf60e473797f5dda5f41d99460cf1c32f34f1c0247d9bd7ff5b";
+
+#[test]
+fn cp() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let (staging, production) = (dir.path().join("staging.ejson"), dir.path().join("production.ejson"));
+    let other = dir.path().join("other.ejson");
+    fs::write(
+        &staging,
+        serde_json::json!({ "_public_key": PUB_KEY, "database": { "password": ENCRYPTED } }).to_string(),
+    )?;
+    fs::write(&production, serde_json::json!({ "_public_key": PUB_KEY }).to_string())?;
+    fs::write(&other, serde_json::json!({ "_public_key": OTHER_PUB_KEY }).to_string())?;
+
+    // within the same file
+    cargo_bin_cmd!()
+        .arg("cp")
+        .arg(&staging)
+        .args(["database.password", "backup"])
+        .assert()
+        .success();
+
+    // to a file with the same public key, the ciphertext is copied as is
+    cargo_bin_cmd!()
+        .arg("cp")
+        .arg(&staging)
+        .args(["database", "database", "--to-file"])
+        .arg(&production)
+        .assert()
+        .success();
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&staging)?)?;
+    assert_eq!(ENCRYPTED, json["backup"]);
+    assert_eq!(ENCRYPTED, json["database"]["password"]);
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&production)?)?;
+    assert_eq!(ENCRYPTED, json["database"]["password"]);
+
+    cargo_bin_cmd!()
+        .arg("cp")
+        .arg(&staging)
+        .args(["backup", "database", "--to-file"])
+        .arg(&production)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("database already exists"));
+
+    // to a file with a different public key, the value is re-encrypted
+    cargo_bin_cmd!()
+        .arg("cp")
+        .arg(&staging)
+        .args(["database.password", "password", "--key-from-stdin", "--to-file"])
+        .arg(&other)
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success();
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&other)?)?;
+    assert_ne!(ENCRYPTED, json["password"]);
+
+    cargo_bin_cmd!()
+        .arg("get")
+        .arg(&other)
+        .arg("password")
+        .args(["--keydir", "examples/data"])
+        .assert()
+        .success()
+        .stdout("test\n");
+
+    Ok(())
+}
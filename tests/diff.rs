@@ -0,0 +1,122 @@
+use std::{fs, process::Command};
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const OTHER_PUB_KEY: &str = "344b86d41cbb5660d98f59b4a7b35f3128e0d0b9c4b06f05ca7ae28b9c7dd72e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn diff() -> Result<()> {
+    let keydir = assert_fs::TempDir::new()?;
+    fs::write(keydir.path().join(PUB_KEY), PRIV_KEY)?;
+
+    let dir = assert_fs::TempDir::new()?;
+    let (from, to) = (dir.path().join("staging.ejson"), dir.path().join("production.ejson"));
+    fs::write(
+        &from,
+        serde_json::json!({ "_public_key": PUB_KEY, "user": ENCRYPTED, "password": "one", "old": "x" }).to_string(),
+    )?;
+    fs::write(
+        &to,
+        serde_json::json!({ "_public_key": PUB_KEY, "user": "test", "password": "two", "new": { "key": "y" } })
+            .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("diff")
+        .args([&from, &to])
+        .arg("--show-values")
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .failure()
+        .stdout("+ new.key (y)\n- old (x)\n~ password (one -> two)\n")
+        .stderr(predicates::str::contains("differ"));
+
+    cargo_bin_cmd!()
+        .arg("diff")
+        .args([&from, &to])
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("~ password ("))
+        .stdout(predicates::str::contains("one").not())
+        .stdout(predicates::str::contains("two").not());
+
+    cargo_bin_cmd!()
+        .arg("diff")
+        .args([&from, &from])
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .success()
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn diff_different_keys() -> Result<()> {
+    let keydir = assert_fs::TempDir::new()?;
+    fs::write(keydir.path().join(PUB_KEY), PRIV_KEY)?;
+
+    let dir = assert_fs::TempDir::new()?;
+    let (from, to) = (dir.path().join("a.ejson"), dir.path().join("b.ejson"));
+    fs::write(
+        &from,
+        serde_json::json!({ "_public_key": PUB_KEY, "a": "1" }).to_string(),
+    )?;
+    fs::write(
+        &to,
+        serde_json::json!({ "_public_key": OTHER_PUB_KEY, "a": "1" }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("diff")
+        .args([&from, &to])
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("private keys for both"));
+
+    Ok(())
+}
+
+#[test]
+fn diff_git_revision() -> Result<()> {
+    let keydir = assert_fs::TempDir::new()?;
+    let repo = assert_fs::TempDir::new()?;
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(repo.path())
+            .output()
+    };
+
+    git(&["init", "-q"])?;
+    fs::write(
+        repo.path().join("secrets.ejson"),
+        serde_json::json!({ "_public_key": PUB_KEY, "a": "1" }).to_string(),
+    )?;
+    git(&["add", "."])?;
+    git(&["commit", "-q", "-m", "initial"])?;
+
+    fs::write(
+        repo.path().join("secrets.ejson"),
+        serde_json::json!({ "_public_key": PUB_KEY, "a": "1", "b": "2" }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .args(["diff", "secrets.ejson@HEAD", "secrets.ejson", "--show-values"])
+        .current_dir(repo.path())
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .failure()
+        .stdout("+ b (2)\n");
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+use serde_json::{Value, json};
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const OTHER_PUB_KEY: &str = "344b86d41cbb5660d98f59b4a7b35f3128e0d0b9c4b06f05ca7ae28b9c7dd72e";
+
+#[test]
+fn doctor() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let keydir = dir.path().join("keys");
+    let key_file = keydir.join(PUB_KEY);
+    fs::create_dir(&keydir)?;
+    fs::set_permissions(&keydir, fs::Permissions::from_mode(0o700))?;
+    fs::write(&key_file, format!("{}\n", PRIV_KEY))?;
+    fs::set_permissions(&key_file, fs::Permissions::from_mode(0o600))?;
+    fs::write(
+        dir.path().join("secrets.ejson"),
+        json!({ "_public_key": PUB_KEY }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("doctor")
+        .arg(dir.path())
+        .env("EJSON_KEYDIR", &keydir)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("(1 usable key(s))"))
+        .stdout(predicates::str::contains("secrets.ejson: private key available"))
+        .stdout(predicates::str::contains("warning").not());
+
+    // Readable by others, and for a different public key than its name.
+    let wrong_file = keydir.join(OTHER_PUB_KEY);
+    fs::rename(&key_file, &wrong_file)?;
+    fs::set_permissions(&wrong_file, fs::Permissions::from_mode(0o644))?;
+
+    cargo_bin_cmd!()
+        .arg("doctor")
+        .arg(dir.path())
+        .arg("--keydir")
+        .arg(&keydir)
+        .assert()
+        .code(5)
+        .stdout(predicates::str::contains(format!("chmod 600 {}", wrong_file.display())))
+        .stdout(predicates::str::contains(format!(
+            "contains the private key for {}",
+            PUB_KEY
+        )))
+        .stdout(predicates::str::contains(format!(
+            "mv {} {}",
+            wrong_file.display(),
+            key_file.display()
+        )))
+        .stdout(predicates::str::contains("secrets.ejson: private key not available"))
+        .stdout(predicates::str::contains(PRIV_KEY).not());
+
+    Ok(())
+}
+
+#[test]
+fn doctor_json() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let keydir = dir.path().join("keys");
+    fs::create_dir(&keydir)?;
+    fs::write(keydir.join(PUB_KEY), format!("{}garbage", PRIV_KEY))?;
+    fs::set_permissions(keydir.join(PUB_KEY), fs::Permissions::from_mode(0o600))?;
+
+    let output = cargo_bin_cmd!()
+        .args(["doctor", "--output", "json", "--keydir"])
+        .arg(&keydir)
+        .assert()
+        .code(5)
+        .get_output()
+        .stdout
+        .clone();
+
+    let report: Value = serde_json::from_slice(&output)?;
+    assert_eq!(json!([]), report["result"]["keys"]);
+    assert_eq!("error", report["result"]["problems"][0]["severity"]);
+    assert_eq!(
+        "contains 7 characters after the private key",
+        report["result"]["problems"][0]["message"]
+    );
+    assert_eq!("validation", report["errors"][0]["kind"]);
+    assert!(!String::from_utf8(output)?.contains(PRIV_KEY));
+
+    Ok(())
+}
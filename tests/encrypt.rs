@@ -91,3 +91,71 @@ fn encrypt_multiple_files() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn encrypt_file_updates_metadata() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "secret": "ssshh",
+            "_meta": { "secret": { "owner": "someone" } }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!().arg("encrypt").arg(file.path()).assert().success();
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    assert!(json["secret"].as_str().unwrap().starts_with("EJ[1:"));
+    assert_eq!("someone", json["_meta"]["secret"]["owner"]);
+    assert!(json["_meta"]["secret"]["rotated_at"].as_str().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn encrypt_recursive() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let secrets = serde_json::json!({ "_public_key": PUB_KEY, "secret": "ssshh" }).to_string();
+    dir.child("services/api/secrets.ejson").write_str(&secrets)?;
+    dir.child("services/web/secrets.ejson").write_str(&secrets)?;
+    dir.child("services/web/skipped.ejson").write_str(&secrets)?;
+    dir.child("services/ignored/secrets.ejson").write_str(&secrets)?;
+    dir.child("services/.gitignore").write_str("ignored/\n")?;
+    dir.child("invalid.ejson").write_str("{")?;
+
+    cargo_bin_cmd!()
+        .arg("encrypt")
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("is a directory (use --recursive"));
+
+    cargo_bin_cmd!()
+        .arg("encrypt")
+        .arg(dir.path())
+        .args(["--recursive", "--exclude", "skipped.ejson"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("3 file(s): 2 changed, 0 unchanged, 1 failed"))
+        .stderr(predicates::str::contains("invalid.ejson: "));
+
+    dir.child("services/api/secrets.ejson")
+        .assert(predicates::str::contains("EJ[1:"));
+    dir.child("services/web/skipped.ejson").assert(secrets.as_str());
+    dir.child("services/ignored/secrets.ejson").assert(secrets.as_str());
+
+    cargo_bin_cmd!()
+        .arg("encrypt")
+        .arg(format!("{}/services/**/*.ejson", dir.path().display()))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("3 file(s): 1 changed, 2 unchanged, 0 failed"));
+
+    dir.child("services/web/skipped.ejson")
+        .assert(predicates::str::contains("EJ[1:"));
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn expiring() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let file = dir.path().join("secrets.ejson");
+    fs::write(
+        &file,
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "database": {
+                "password": ENCRYPTED,
+                "user": ENCRYPTED,
+                "_meta": {
+                    "password": { "owner": "team-db", "expires_at": "2000-01-01" },
+                    "user": { "expires_at": "2999-01-01T00:00:00Z" }
+                }
+            },
+            "api_key": ENCRYPTED,
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("expiring")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(format!(
+            r"^{}: database.password expires 2000-01-01 \(\d+ days ago\), owned by team-db\n$",
+            regex::escape(&file.display().to_string())
+        ))?);
+
+    let output = cargo_bin_cmd!()
+        .arg("expiring")
+        .arg(&file)
+        .args(["--within", "1000000d", "--format", "json"])
+        .output()?;
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let secrets = json["secrets"].as_array().unwrap();
+    assert_eq!(2, secrets.len());
+    assert_eq!("database.password", secrets[0]["key"]);
+    assert_eq!("team-db", secrets[0]["owner"]);
+    assert_eq!(true, secrets[0]["expired"]);
+    assert_eq!("database.user", secrets[1]["key"]);
+    assert_eq!(false, secrets[1]["expired"]);
+
+    cargo_bin_cmd!()
+        .arg("expiring")
+        .arg(&file)
+        .args(["--within", "soon"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("expected a duration"));
+
+    cargo_bin_cmd!()
+        .arg("expiring")
+        .arg(&file)
+        .args(["--within", "99999999999999w"])
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("expected a duration"));
+
+    Ok(())
+}
+
+#[test]
+fn metadata_follows_values() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "password": ENCRYPTED,
+            "token": ENCRYPTED,
+            "_meta": { "password": { "owner": "team-db" }, "token": {} }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("set")
+        .arg(file.path())
+        .arg("password")
+        .write_stdin("rotated")
+        .assert()
+        .success();
+    cargo_bin_cmd!()
+        .arg("set")
+        .arg(file.path())
+        .arg("other")
+        .write_stdin("value")
+        .assert()
+        .success();
+    cargo_bin_cmd!()
+        .arg("mv")
+        .arg(file.path())
+        .args(["password", "db_password"])
+        .assert()
+        .success();
+    cargo_bin_cmd!()
+        .arg("rm")
+        .arg(file.path())
+        .arg("token")
+        .assert()
+        .success();
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    let meta = json["_meta"].as_object().unwrap();
+
+    // rotated_at is only tracked for values with metadata, which is moved and removed with them
+    assert_eq!(vec!["db_password"], meta.keys().collect::<Vec<_>>());
+    assert_eq!("team-db", meta["db_password"]["owner"]);
+    assert!(meta["db_password"]["rotated_at"].as_str().unwrap().ends_with('Z'));
+    assert!(json["db_password"].as_str().unwrap().starts_with("EJ[1:"));
+
+    Ok(())
+}
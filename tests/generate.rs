@@ -0,0 +1,69 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+
+#[test]
+fn generate() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(file.path(), serde_json::json!({ "_public_key": PUB_KEY }).to_string())?;
+
+    let generate = |key: &str, args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("generate").arg(file.path()).arg(key).args(args);
+        cmd
+    };
+    let get = |key: &str| {
+        let output = cargo_bin_cmd!()
+            .arg("get")
+            .arg(file.path())
+            .arg(key)
+            .arg("--key-from-stdin")
+            .write_stdin(PRIV_KEY)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim_end().to_string()
+    };
+
+    let output = generate("password", &[]).output()?;
+    assert!(output.status.success());
+    assert!(!String::from_utf8(output.stdout)?.contains(&get("password")));
+    assert_eq!(32, get("password").len());
+
+    let output = generate(
+        "database.key",
+        &["--charset", "hex", "--length", "16", "--create", "--show"],
+    )
+    .output()?;
+    assert!(output.status.success());
+    assert_eq!(32, get("database.key").len());
+    assert!(String::from_utf8(output.stdout)?.ends_with(&format!("{}\n", get("database.key"))));
+
+    generate("passphrase", &["--charset", "words"]).assert().success();
+    generate("id", &["--kind", "uuid"]).assert().success();
+    generate("signing", &["--kind", "ed25519"]).assert().success();
+
+    // existing values are kept with --if-missing, and only replaced with --force
+    let password = get("password");
+    generate("password", &["--if-missing"]).assert().success();
+    generate("password", &[])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("password already exists"));
+    assert_eq!(password, get("password"));
+    generate("password", &["--force"]).assert().success();
+    assert_ne!(password, get("password"));
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    assert!(json["password"].as_str().unwrap().starts_with("EJ[1:"));
+    assert!(json["passphrase"].as_str().unwrap().starts_with("EJ[1:"));
+    assert!(json["signing"]["private_key"].as_str().unwrap().starts_with("EJ[1:"));
+    assert!(!json["signing"]["_public_key"].as_str().unwrap().starts_with("EJ[1:"));
+    assert_eq!(6, get("passphrase").split('.').count());
+    assert_eq!(36, get("id").len());
+
+    Ok(())
+}
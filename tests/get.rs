@@ -0,0 +1,48 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn get() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "database": { "user": ENCRYPTED, "config.json": ENCRYPTED },
+            "broken": "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:AAAA]"
+        })
+        .to_string(),
+    )?;
+
+    let get = |key: &str| {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("get")
+            .arg(file.path())
+            .arg(key)
+            .arg("--key-from-stdin")
+            .write_stdin(PRIV_KEY);
+        cmd
+    };
+
+    // only the requested value is decrypted, so the broken one doesn't matter
+    get("database.user").assert().success().stdout("test\n");
+    get("database.[config.json]").assert().success().stdout("test\n");
+    get("database")
+        .assert()
+        .success()
+        .stdout("{\n  \"user\": \"test\",\n  \"config.json\": \"test\"\n}\n");
+
+    get("database.missing")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("database.missing not found"));
+
+    Ok(())
+}
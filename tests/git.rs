@@ -0,0 +1,212 @@
+use std::{fs, process::Command};
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn git_textconv() -> Result<()> {
+    let keydir = assert_fs::TempDir::new()?;
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({ "_public_key": PUB_KEY, "username": ENCRYPTED }).to_string(),
+    )?;
+
+    // without the key, values are redacted
+    cargo_bin_cmd!()
+        .arg("git-textconv")
+        .arg(file.path())
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(r#""username": "REDACTED["#))
+        .stdout(predicates::str::contains("EJ[").not());
+
+    fs::write(keydir.path().join(PUB_KEY), PRIV_KEY)?;
+
+    cargo_bin_cmd!()
+        .arg("git-textconv")
+        .arg(file.path())
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(r#""username": "test""#));
+
+    // values that can't be decrypted are redacted instead of failing the diff
+    let corrupted = ENCRYPTED.replace(":JNCz", ":KNCz");
+    fs::write(
+        file.path(),
+        serde_json::json!({ "_public_key": PUB_KEY, "username": ENCRYPTED, "password": corrupted }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("git-textconv")
+        .arg(file.path())
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(r#""username": "test""#))
+        .stdout(predicates::str::contains(r#""password": "REDACTED["#));
+
+    Ok(())
+}
+
+#[test]
+fn git_textconv_invalid_json() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(file.path(), "<<<<<<< HEAD\n{}")?;
+
+    cargo_bin_cmd!()
+        .arg("git-textconv")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("<<<<<<< HEAD\n{}");
+
+    Ok(())
+}
+
+#[test]
+fn git_setup() -> Result<()> {
+    let repo = assert_fs::TempDir::new()?;
+    Command::new("git")
+        .arg("init")
+        .arg("-q")
+        .current_dir(repo.path())
+        .status()?;
+    fs::write(repo.path().join(".gitattributes"), "*.png binary")?;
+
+    cargo_bin_cmd!()
+        .args(["git", "setup"])
+        .current_dir(repo.path())
+        .assert()
+        .success();
+
+    // running it again doesn't duplicate anything
+    cargo_bin_cmd!()
+        .args(["git", "setup"])
+        .current_dir(repo.path())
+        .assert()
+        .success();
+
+    assert_eq!(
+        "*.png binary\n*.ejson diff=ejson merge=ejson\n",
+        fs::read_to_string(repo.path().join(".gitattributes"))?
+    );
+
+    let output = Command::new("git")
+        .args(["config", "diff.ejson.textconv"])
+        .current_dir(repo.path())
+        .output()?;
+    assert_eq!("rejson git-textconv\n", String::from_utf8(output.stdout)?);
+
+    let config = |key: &str| -> Result<String> {
+        let output = Command::new("git")
+            .args(["config", key])
+            .current_dir(repo.path())
+            .output()?;
+        Ok(String::from_utf8(output.stdout)?)
+    };
+    assert_eq!("EJSON merge\n", config("merge.ejson.name")?);
+    assert_eq!("rejson git-merge %O %A %B\n", config("merge.ejson.driver")?);
+
+    Ok(())
+}
+
+/// Encrypts the value with the test public key.
+fn encrypt(value: &str) -> Result<String> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({ "_public_key": PUB_KEY, "value": value }).to_string(),
+    )?;
+
+    cargo_bin_cmd!().arg("encrypt").arg(file.path()).assert().success();
+
+    let encrypted: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    Ok(encrypted["value"].as_str().unwrap().to_string())
+}
+
+#[test]
+fn git_merge() -> Result<()> {
+    let keydir = assert_fs::TempDir::new()?;
+    fs::write(keydir.path().join(PUB_KEY), PRIV_KEY)?;
+
+    let dir = assert_fs::TempDir::new()?;
+    let (base, ours, theirs) = (dir.path().join("O"), dir.path().join("A"), dir.path().join("B"));
+
+    let (same_ours, same_theirs) = (encrypt("same")?, encrypt("same")?);
+    fs::write(
+        &base,
+        format!(
+            "{{\n    \"_public_key\": \"{}\",\n    \"a\": \"{}\"\n}}\n",
+            PUB_KEY, ENCRYPTED
+        ),
+    )?;
+    fs::write(
+        &ours,
+        format!(
+            "{{\n    \"_public_key\": \"{}\",\n    \"a\": \"{}\",\n    \"ours\": \"{}\",\n    \"same\": \"{}\"\n}}\n",
+            PUB_KEY, ENCRYPTED, ENCRYPTED, same_ours
+        ),
+    )?;
+    fs::write(
+        &theirs,
+        serde_json::json!({ "_public_key": PUB_KEY, "a": ENCRYPTED, "theirs": ENCRYPTED, "same": same_theirs })
+            .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("git-merge")
+        .args([&base, &ours, &theirs])
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .success();
+
+    assert_eq!(
+        format!(
+            "{{\n    \"_public_key\": \"{}\",\n    \"a\": \"{}\",\n    \"ours\": \"{}\",\n    \"same\": \"{}\",\n    \"theirs\": \"{}\"\n}}\n",
+            PUB_KEY, ENCRYPTED, ENCRYPTED, same_ours, ENCRYPTED
+        ),
+        fs::read_to_string(&ours)?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn git_merge_conflict() -> Result<()> {
+    let keydir = assert_fs::TempDir::new()?;
+    let dir = assert_fs::TempDir::new()?;
+    let (base, ours, theirs) = (dir.path().join("O"), dir.path().join("A"), dir.path().join("B"));
+
+    // Without the key, re-encrypted values can't be compared.
+    let (same_ours, same_theirs) = (encrypt("same")?, encrypt("same")?);
+    fs::write(&base, "")?;
+    fs::write(
+        &ours,
+        serde_json::json!({ "_public_key": PUB_KEY, "nested": { "a": same_ours } }).to_string(),
+    )?;
+    fs::write(
+        &theirs,
+        serde_json::json!({ "_public_key": PUB_KEY, "nested": { "a": same_theirs } }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("git-merge")
+        .args([&base, &ours, &theirs])
+        .env("EJSON_KEYDIR", keydir.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("CONFLICT: nested.a"));
+
+    assert!(fs::read_to_string(&ours)?.contains(&same_ours));
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use rejson::{Key, SecretsFile};
+use serde_json::json;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+
+#[test]
+fn init() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let keydir = dir.path().join("keys");
+    let file = dir.path().join("secrets.ejson");
+
+    cargo_bin_cmd!()
+        .arg("init")
+        .arg(&file)
+        .arg("--keydir")
+        .arg(&keydir)
+        .args(["--env", "--kube-secrets"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Public Key: "));
+
+    let secrets_file = SecretsFile::load(&file)?;
+    let public_key = secrets_file.public_key().expect("a valid public key");
+    assert_eq!(
+        json!({ "_public_key": public_key.to_string(), "environment": {}, "kubernetes": {} }).to_string(),
+        secrets_file.to_json().to_string()
+    );
+
+    let key_file = keydir.join(public_key.to_string());
+    assert_eq!(0o600, fs::metadata(&key_file)?.permissions().mode() & 0o777);
+    assert_eq!(0o700, fs::metadata(&keydir)?.permissions().mode() & 0o777);
+    assert!(rejson::load_private_key(&secrets_file, &keydir.display().to_string()).is_ok());
+
+    // The new file can be encrypted and decrypted.
+    fs::write(
+        &file,
+        json!({ "_public_key": public_key.to_string(), "password": "hunter2" }).to_string(),
+    )?;
+    cargo_bin_cmd!().arg("encrypt").arg(&file).assert().success();
+    cargo_bin_cmd!()
+        .args(["get", "--keydir"])
+        .arg(&keydir)
+        .arg(&file)
+        .arg("password")
+        .assert()
+        .success()
+        .stdout("hunter2\n");
+
+    // Existing files are never overwritten.
+    let contents = fs::read_to_string(&file)?;
+    cargo_bin_cmd!()
+        .arg("init")
+        .arg(&file)
+        .arg("--keydir")
+        .arg(&keydir)
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("already exists"));
+    assert_eq!(contents, fs::read_to_string(&file)?);
+    assert_eq!(1, fs::read_dir(&keydir)?.count());
+
+    Ok(())
+}
+
+#[test]
+fn init_failures_leave_nothing_behind() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let keydir = dir.path().join("keys");
+
+    // The file can't be created, so no key is written.
+    cargo_bin_cmd!()
+        .arg("init")
+        .arg(dir.path().join("missing/secrets.ejson"))
+        .arg("--keydir")
+        .arg(&keydir)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("creating"));
+    assert!(!keydir.exists());
+
+    // The key can't be written, so the file is removed.
+    fs::write(&keydir, "not a directory")?;
+    let file = dir.path().join("secrets.ejson");
+    cargo_bin_cmd!()
+        .arg("init")
+        .arg(&file)
+        .arg("--keydir")
+        .arg(&keydir)
+        .assert()
+        .failure();
+    assert!(!file.exists());
+
+    Ok(())
+}
+
+#[test]
+fn init_with_public_key() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let keydir = dir.path().join("keys");
+    let file = dir.path().join("secrets.ejson");
+
+    cargo_bin_cmd!()
+        .arg("init")
+        .arg(&file)
+        .arg("--keydir")
+        .arg(&keydir)
+        .args(["--public-key", PUB_KEY])
+        .assert()
+        .success();
+
+    assert_eq!(Some(PUB_KEY.parse::<Key>()?), SecretsFile::load(&file)?.public_key());
+    assert!(!keydir.exists(), "no key pair is generated");
+
+    cargo_bin_cmd!()
+        .args(["init", "other.ejson", "--public-key", "nope"])
+        .current_dir(dir.path())
+        .assert()
+        .code(2);
+
+    // Rules in the config set the public key of new files.
+    fs::write(
+        dir.path().join(".rejson.toml"),
+        format!("[[rules]]\npath = \"services/**\"\npublic_key = \"{}\"\n", PUB_KEY),
+    )?;
+    fs::create_dir(dir.path().join("services"))?;
+
+    cargo_bin_cmd!()
+        .args(["init", "services/api.ejson", "--output", "json"])
+        .current_dir(dir.path())
+        .env_remove("EJSON_KEYDIR")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!("\"public_key\": \"{}\"", PUB_KEY)));
+    assert_eq!(
+        Some(PUB_KEY.parse::<Key>()?),
+        SecretsFile::load(dir.path().join("services/api.ejson"))?.public_key()
+    );
+
+    Ok(())
+}
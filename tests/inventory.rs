@@ -0,0 +1,69 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const OTHER_PUB_KEY: &str = "344b86d41cbb5660d98f59b4a7b35f3128e0d0b9c4b06f05ca7ae28b9c7dd72e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn inventory() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    fs::create_dir(dir.path().join("api"))?;
+    fs::write(
+        dir.path().join("api/production.ejson"),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "password": ENCRYPTED,
+            "_meta": { "password": { "owner": "team-api", "description": "Database password" } }
+        })
+        .to_string(),
+    )?;
+    fs::write(
+        dir.path().join("staging.ejson"),
+        serde_json::json!({ "_public_key": OTHER_PUB_KEY, "token": "plaintext" }).to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("inventory")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains(format!("(`{}`)", PUB_KEY))
+                .and(predicates::str::contains(
+                    "| password | encrypted | 1 | team-api | Database password |",
+                ))
+                .and(predicates::str::contains("| token | plaintext |  |  |  |"))
+                .and(predicates::str::contains(ENCRYPTED).not()),
+        );
+
+    let output = cargo_bin_cmd!()
+        .arg("inventory")
+        .arg(dir.path())
+        .args(["--format", "csv"])
+        .output()?;
+    assert!(output.status.success());
+
+    let csv = String::from_utf8(output.stdout)?;
+    let rows = csv.lines().collect::<Vec<_>>();
+    assert_eq!(3, rows.len());
+    assert!(rows[0].starts_with("fingerprint,public_key,file,key"));
+    assert!(
+        rows.iter()
+            .any(|row| row.contains("password,encrypted,1,team-api,Database password"))
+    );
+
+    let output = cargo_bin_cmd!()
+        .arg("inventory")
+        .arg(dir.path())
+        .args(["--format", "json"])
+        .output()?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(2, json["public_keys"].as_array().unwrap().len());
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::fs;
+use std::{fs, os::unix::fs::PermissionsExt};
 
 use anyhow::Result;
 use assert_cmd::cargo_bin_cmd;
@@ -28,8 +28,9 @@ fn generate_and_write_file_to_keydir() -> Result<()> {
         .success()
         .stdout(predicates::str::contains("Private Key:").not());
 
-    let paths = fs::read_dir(temp.path())?;
-    assert_eq!(1, paths.count());
+    let paths = fs::read_dir(temp.path())?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(1, paths.len());
+    assert_eq!(0o600, paths[0].metadata()?.permissions().mode() & 0o777);
 
     Ok(())
 }
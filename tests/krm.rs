@@ -0,0 +1,104 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+
+#[test]
+fn krm_function() -> Result<()> {
+    let key_file = assert_fs::NamedTempFile::new(PUB_KEY)?;
+    fs::write(key_file.path(), PRIV_KEY)?;
+
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "database": {
+                    "DATABASE_URL": "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:0w+6gl3gXIOQohjqZnmih8ZLWPVffurJ:7U6ZEcttjrkS5sA73T/y/hESIaoxJUA320XqBoFWvw==]"
+                },
+                "_meta": {
+                    "database": { "owner": "platform" }
+                }
+            }
+        })
+        .to_string(),
+    )?;
+
+    let input = format!(
+        r#"apiVersion: config.kubernetes.io/v1
+kind: ResourceList
+items:
+- apiVersion: v1
+  kind: ConfigMap
+  metadata:
+    name: existing
+functionConfig:
+  apiVersion: rejson.pseudomuto.com/v1
+  kind: SecretsGenerator
+  metadata:
+    name: secrets
+  spec:
+    files:
+    - {}
+    keydir: {}
+    namespace: testing
+"#,
+        file.path().display(),
+        key_file.parent().unwrap().display(),
+    );
+
+    let exp = r#"items:
+- apiVersion: v1
+  kind: ConfigMap
+  metadata:
+    name: existing
+- apiVersion: v1
+  kind: Secret
+  metadata:
+    name: database
+    namespace: testing
+  data:
+    DATABASE_URL: cGdzcWw6Ly9zb21lLWRi
+"#;
+
+    cargo_bin_cmd!()
+        .arg("krm")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(exp));
+
+    Ok(())
+}
+
+#[test]
+fn krm_function_errors() -> Result<()> {
+    let input = r#"apiVersion: config.kubernetes.io/v1
+kind: ResourceList
+items: []
+functionConfig:
+  apiVersion: rejson.pseudomuto.com/v1
+  kind: SecretsGenerator
+  metadata:
+    name: secrets
+  spec:
+    files:
+    - does-not-exist.ejson
+"#;
+
+    cargo_bin_cmd!()
+        .arg("krm")
+        .write_stdin(input)
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("results:\n- message:"))
+        .stdout(predicates::str::contains(
+            "  severity: error\n  file:\n    path: does-not-exist.ejson\n",
+        ));
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::fs;
 
 use anyhow::Result;
 use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
 
 const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
 const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
@@ -115,3 +116,553 @@ data:
 
     Ok(())
 }
+
+#[test]
+fn kube_secrets_metadata() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "app": {
+                    "_immutable": true,
+                    "_labels": {
+                        "app.kubernetes.io/name": "app"
+                    },
+                    "_annotations": {
+                        "owner": "platform"
+                    },
+                    "TOKEN": "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]"
+                },
+                "_meta": {
+                    "app": { "owner": "platform" }
+                }
+            }
+        })
+        .to_string(),
+    )?;
+
+    let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: app
+  labels:
+    app.kubernetes.io/name: app
+    team: platform
+  annotations:
+    owner: platform
+immutable: true
+stringData:
+  TOKEN: test"#;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .arg("--label")
+        .arg("team=platform")
+        .arg("--string-data")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(exp))
+        .stdout(predicates::str::contains("namespace").not());
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_invalid() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "good": {
+                    "TOKEN": "value"
+                },
+                "Not_Valid": {
+                    "TOKEN": "value",
+                    "bad/key": "value"
+                }
+            }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .failure()
+        .stdout(predicates::str::is_empty())
+        .stderr(predicates::str::contains("kubernetes.Not_Valid: secret name"))
+        .stderr(predicates::str::contains("kubernetes.Not_Valid.bad/key: key must"));
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_namespaces() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "_namespaces": true,
+                "production": {
+                    "db-creds": {
+                        "PASSWORD": "production"
+                    }
+                },
+                "staging": {
+                    "db-creds": {
+                        "PASSWORD": "staging"
+                    }
+                }
+            }
+        })
+        .to_string(),
+    )?;
+
+    let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: db-creds
+  namespace: production
+data:
+  PASSWORD: cHJvZHVjdGlvbg==
+---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: db-creds
+  namespace: staging
+data:
+  PASSWORD: c3RhZ2luZw=="#;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(exp));
+
+    let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: db-creds
+  namespace: preview
+data:
+  PASSWORD: c3RhZ2luZw==
+"#;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .arg("--filter-namespace")
+        .arg("staging")
+        .arg("--namespace")
+        .arg("preview")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::diff(format!("{}\n", exp)));
+
+    // Overriding the namespace without filtering makes both definitions collide.
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .arg("--namespace")
+        .arg("preview")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("secret preview/db-creds is already defined"));
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_binary_values() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "keystore": {
+                    "keystore.p12": "base64:3q2+7w=="
+                }
+            }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!().arg("encrypt").arg(file.path()).assert().success();
+
+    let contents: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    let value = contents["kubernetes"]["keystore"]["keystore.p12"].as_str().unwrap();
+    assert!(value.starts_with("EJ[1:"));
+
+    let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: keystore
+data:
+  keystore.p12: 3q2+7w==
+
+"#;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(exp);
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_tls() -> Result<()> {
+    let cert = fs::read_to_string("tests/data/tls/ec.crt")?;
+    let key = fs::read_to_string("tests/data/tls/ec.key")?;
+    let other_cert = fs::read_to_string("tests/data/tls/other.crt")?;
+
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    let run = |cert: &str, key: &str| -> Result<assert_cmd::assert::Assert> {
+        fs::write(
+            file.path(),
+            serde_json::json!({
+                "_public_key": PUB_KEY,
+                "kubernetes": {
+                    "tls-cert": {
+                        "_type": "kubernetes.io/tls",
+                        "tls.crt": cert,
+                        "tls.key": key
+                    }
+                }
+            })
+            .to_string(),
+        )?;
+
+        Ok(cargo_bin_cmd!()
+            .arg("kube-secrets")
+            .arg(file.path())
+            .arg("--key-from-stdin")
+            .arg("--string-data")
+            .write_stdin(PRIV_KEY)
+            .assert())
+    };
+
+    run(&cert, &key)?.success().stdout(predicates::str::contains(
+        "  tls.crt: |\n    -----BEGIN CERTIFICATE-----\n",
+    ));
+
+    // PEM values compacted onto a single line are expanded.
+    run(&cert.trim().replace('\n', r"\n"), &key.trim().replace('\n', r"\n"))?
+        .success()
+        .stdout(predicates::str::contains(
+            "  tls.crt: |-\n    -----BEGIN CERTIFICATE-----\n",
+        ));
+
+    run(&other_cert, &key)?.code(5).stderr(predicates::str::contains(
+        "kubernetes.tls-cert.[tls.key]: private key does not match the certificate",
+    ));
+
+    run("Some Certificate", &key)?.code(5).stderr(predicates::str::contains(
+        "kubernetes.tls-cert.[tls.crt]: must be a PEM encoded certificate",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_docker_registry() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "registry": {
+                    "_type": "kubernetes.io/dockerconfigjson",
+                    "server": "registry.example.com",
+                    "username": "user",
+                    "password": "pass"
+                },
+                "incomplete": {
+                    "_type": "kubernetes.io/dockerconfigjson",
+                    "server": "registry.example.com"
+                }
+            }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "kubernetes.incomplete: kubernetes.io/dockerconfigjson secrets require username",
+        ));
+
+    let mut json: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    json["kubernetes"].as_object_mut().unwrap().remove("incomplete");
+    fs::write(file.path(), json.to_string())?;
+
+    let exp = r#"---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: registry
+type: kubernetes.io/dockerconfigjson
+stringData:
+  .dockerconfigjson: '{"auths":{"registry.example.com":{"username":"user","password":"pass","auth":"dXNlcjpwYXNz"}}}'"#;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .arg("--string-data")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(exp));
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_out_dir() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    let write_secrets = |secrets: serde_json::Value| {
+        fs::write(
+            file.path(),
+            serde_json::json!({ "_public_key": PUB_KEY, "kubernetes": secrets }).to_string(),
+        )
+    };
+
+    let run = || {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("kube-secrets")
+            .arg(file.path())
+            .arg("--key-from-stdin")
+            .arg("--out-dir")
+            .arg(dir.path())
+            .arg("--kustomization")
+            .write_stdin(PRIV_KEY);
+        cmd
+    };
+
+    write_secrets(serde_json::json!({
+        "_namespaces": true,
+        "app": { "TOKEN": "abc" },
+        "staging": { "db": { "PASSWORD": "secret" } }
+    }))?;
+
+    run()
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("app.yaml"))
+        .stdout(predicates::str::contains("staging/db.yaml"));
+
+    let app = fs::read_to_string(dir.path().join("app.yaml"))?;
+    assert!(app.contains("name: app\n"));
+    assert!(app.contains("rejson.pseudomuto.com/source:"));
+    assert!(dir.path().join("staging/db.yaml").exists());
+
+    let kustomization = fs::read_to_string(dir.path().join("kustomization.yaml"))?;
+    assert!(kustomization.contains("- app.yaml\n- staging/db.yaml\n"));
+
+    // nothing changed, so nothing is rewritten
+    run().assert().success().stdout("");
+
+    // removed secrets are cleaned up
+    write_secrets(serde_json::json!({ "app": { "TOKEN": "abc" } }))?;
+    run()
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Removed"))
+        .stdout(predicates::str::contains("staging/db.yaml"));
+
+    assert!(!dir.path().join("staging").exists());
+    assert!(dir.path().join("app.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_out_dir_rejects_namespace_traversal() -> Result<()> {
+    let root = assert_fs::TempDir::new()?;
+    let dir = root.path().join("a/b/out");
+    let file = root.path().join("secrets.ejson");
+    fs::write(
+        &file,
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": { "app": { "_namespace": "../../escaped", "TOKEN": "abc" } }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("kube-secrets")
+        .arg(&file)
+        .arg("--key-from-stdin")
+        .arg("--out-dir")
+        .arg(&dir)
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .code(5)
+        .stderr(predicates::str::contains("must be a valid DNS-1123 label"));
+
+    assert!(!root.path().join("a/escaped").exists());
+    assert!(!dir.exists());
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_out_dir_from_another_directory() -> Result<()> {
+    let root = assert_fs::TempDir::new()?;
+    let sub = root.path().join("sub");
+    fs::create_dir(&sub)?;
+    fs::write(
+        root.path().join("secrets.ejson"),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": { "app": { "TOKEN": "abc" }, "db": { "PASSWORD": "secret" } }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .current_dir(root.path())
+        .args([
+            "kube-secrets",
+            "./secrets.ejson",
+            "--key-from-stdin",
+            "--out-dir",
+            "out",
+        ])
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success();
+
+    let app = fs::read_to_string(root.path().join("out/app.yaml"))?;
+    assert!(
+        app.contains("rejson.pseudomuto.com/source: ../secrets.ejson\n"),
+        "{}",
+        app
+    );
+
+    fs::write(
+        root.path().join("secrets.ejson"),
+        serde_json::json!({ "_public_key": PUB_KEY, "kubernetes": { "app": { "TOKEN": "abc" } } }).to_string(),
+    )?;
+
+    // the same file, referenced from another working directory, still cleans up after itself
+    cargo_bin_cmd!()
+        .current_dir(&sub)
+        .args([
+            "kube-secrets",
+            "../secrets.ejson",
+            "--key-from-stdin",
+            "--out-dir",
+            "../out",
+        ])
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Removed"));
+
+    assert!(root.path().join("out/app.yaml").exists());
+    assert!(!root.path().join("out/db.yaml").exists());
+
+    Ok(())
+}
+
+#[test]
+fn kube_secrets_out_dir_conflicts() {
+    cargo_bin_cmd!()
+        .args(["kube-secrets", "secrets.ejson", "--out", "a.yaml", "--out-dir", "out"])
+        .assert()
+        .failure();
+
+    cargo_bin_cmd!()
+        .args(["kube-secrets", "secrets.ejson", "--kustomization"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn kube_secrets_diff() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "kubernetes": {
+                "app": { "TOKEN": "abc", "NEW": "value" }
+            }
+        })
+        .to_string(),
+    )?;
+
+    let manifest = assert_fs::NamedTempFile::new("manifest.yaml")?;
+    let diff = || {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("kube-secrets")
+            .arg(file.path())
+            .arg("--key-from-stdin")
+            .arg("--diff")
+            .arg(manifest.path())
+            .write_stdin(PRIV_KEY);
+        cmd
+    };
+
+    fs::write(
+        manifest.path(),
+        "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\ndata:\n  TOKEN: eHl6\n---\napiVersion: v1\nkind: Secret\nmetadata:\n  name: old\n  namespace: ns\nstringData:\n  KEY: value\n",
+    )?;
+
+    diff()
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("~ app\n  + NEW ("))
+        .stdout(predicates::str::contains("  ~ TOKEN ("))
+        .stdout(predicates::str::contains("- ns/old\n  - KEY ("))
+        .stdout(predicates::str::contains("abc").not())
+        .stderr(predicates::str::contains("differ"));
+
+    fs::write(
+        manifest.path(),
+        "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\nstringData:\n  TOKEN: abc\n  NEW: value\n",
+    )?;
+
+    diff().assert().success().stdout("");
+
+    Ok(())
+}
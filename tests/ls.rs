@@ -0,0 +1,47 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn ls() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "database": { "password": ENCRYPTED, "port": 5432 },
+            "tls": { "tls.crt": "plaintext" }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!().arg("ls").arg(file.path()).assert().success().stdout(
+        "plain      _public_key\n\
+             encrypted  database.password\n\
+             plain      database.port\n\
+             plain      tls.[tls.crt]\n",
+    );
+
+    cargo_bin_cmd!()
+        .arg("ls")
+        .arg(file.path())
+        .arg("database")
+        .assert()
+        .success()
+        .stdout("encrypted  database.password\nplain      database.port\n");
+
+    cargo_bin_cmd!()
+        .arg("ls")
+        .arg(file.path())
+        .arg("missing")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("missing not found"));
+
+    Ok(())
+}
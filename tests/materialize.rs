@@ -0,0 +1,77 @@
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+
+#[test]
+fn materialize() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let out = dir.path().join("secrets");
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    let write_secrets = |environment: serde_json::Value| {
+        fs::write(
+            file.path(),
+            serde_json::json!({ "_public_key": PUB_KEY, "environment": environment }).to_string(),
+        )
+    };
+
+    let run = || {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("materialize")
+            .arg(file.path())
+            .args(["--key-from-stdin", "--root", "environment", "--clean", "--dir"])
+            .arg(&out)
+            .write_stdin(PRIV_KEY);
+        cmd
+    };
+
+    write_secrets(serde_json::json!({
+        "API_KEY": "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]",
+        "db": { "password": "secret" }
+    }))?;
+
+    run()
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("API_KEY"))
+        .stdout(predicates::str::contains("db/password"));
+
+    assert_eq!("test", fs::read_to_string(out.join("API_KEY"))?);
+    assert_eq!("secret", fs::read_to_string(out.join("db/password"))?);
+    assert_eq!(0o400, fs::metadata(out.join("API_KEY"))?.permissions().mode() & 0o777);
+    assert_eq!(0o700, fs::metadata(out.join("db"))?.permissions().mode() & 0o777);
+
+    write_secrets(serde_json::json!({ "API_KEY": "changed" }))?;
+    run()
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Wrote"))
+        .stdout(predicates::str::contains("Removed"));
+
+    assert_eq!("changed", fs::read_to_string(out.join("API_KEY"))?);
+    assert!(!out.join("db").exists());
+
+    Ok(())
+}
+
+#[test]
+fn materialize_missing_root() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(file.path(), serde_json::json!({ "_public_key": PUB_KEY }).to_string())?;
+
+    cargo_bin_cmd!()
+        .arg("materialize")
+        .arg(file.path())
+        .args(["--key-from-stdin", "--root", "environment", "--dir"])
+        .arg(dir.path())
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("environment not found"));
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn mv() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({ "_public_key": PUB_KEY, "password": ENCRYPTED, "user": ENCRYPTED, "_note": "plain" })
+            .to_string(),
+    )?;
+
+    let mv = |from: &str, to: &str, args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("mv").arg(file.path()).args([from, to]).args(args);
+        cmd
+    };
+
+    mv("password", "database.password", &[])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("database not found"));
+    mv("password", "user", &[])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("user already exists"));
+
+    mv("password", "database.password", &["--create"]).assert().success();
+    mv("user", "database.user", &[]).assert().success();
+    mv("_note", "note", &[]).assert().success();
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(file.path())?)?;
+    // encrypted values are moved as is
+    assert_eq!(
+        serde_json::json!({ "password": ENCRYPTED, "user": ENCRYPTED }),
+        json["database"]
+    );
+    assert!(json.get("password").is_none());
+    // plaintext moved to a key that's encrypted is encrypted
+    assert!(json["note"].as_str().unwrap().starts_with("EJ[1:"));
+
+    Ok(())
+}
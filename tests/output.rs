@@ -0,0 +1,243 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use rejson::Key;
+use serde_json::{Value, json};
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+// Encrypted to the key in examples/data rather than PUB_KEY.
+const OTHER_ENCRYPTED: &str =
+    "EJ[1:kzoS8R+Pg09zJm20V4NgUkzaYW1qIkeZjCUZWNzhL3k=:tLyPsfYvMKrHklVhsf6PmdUTD4xSb/Ny:xRas2Ig1wqN7BtZOXkgqFBPR/g==]";
+
+fn parse(output: &[u8]) -> Value {
+    serde_json::from_slice(output).expect("stdout should be a single JSON object")
+}
+
+#[test]
+fn encrypt_json() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        json!({ "_public_key": PUB_KEY, "user": ENCRYPTED, "password": "hunter2", "_comment": "skipped" }).to_string(),
+    )?;
+
+    let output = cargo_bin_cmd!()
+        .args(["encrypt", "--output", "json"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let report = parse(&output);
+    assert_eq!(true, report["ok"]);
+    assert_eq!(0, report["exit_code"]);
+    assert_eq!(file.path().display().to_string(), report["files"][0]["path"]);
+    assert_eq!("written", report["files"][0]["status"]);
+    assert_eq!(fs::metadata(file.path())?.len(), report["files"][0]["bytes"]);
+    assert_eq!(json!({ "encrypted": 1, "skipped": 1 }), report["values"]);
+    assert_eq!(json!([PUB_KEY.parse::<Key>()?.fingerprint()]), report["fingerprints"]);
+
+    // Nothing left to encrypt.
+    let output = cargo_bin_cmd!()
+        .args(["--output=json", "encrypt"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let report = parse(&output);
+    assert_eq!("unchanged", report["files"][0]["status"]);
+    assert_eq!(json!({ "encrypted": 0, "skipped": 2 }), report["values"]);
+
+    Ok(())
+}
+
+#[test]
+fn decrypt_json() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        json!({ "_public_key": PUB_KEY, "database": { "password": ENCRYPTED } }).to_string(),
+    )?;
+
+    let output = cargo_bin_cmd!()
+        .args(["decrypt", "--key-from-stdin", "--output", "json"])
+        .arg(file.path())
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let report = parse(&output);
+    assert_eq!(
+        json!({ "_public_key": PUB_KEY, "database": { "password": "test" } }),
+        report["result"]
+    );
+    assert_eq!(json!([]), report["errors"]);
+    assert_eq!(Value::Null, report["values"]);
+
+    Ok(())
+}
+
+#[test]
+fn exit_codes() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let file = dir.path().join("secrets.ejson");
+    fs::write(
+        &file,
+        json!({ "_public_key": PUB_KEY, "database": { "password": OTHER_ENCRYPTED } }).to_string(),
+    )?;
+
+    // Usage errors, including the ones reported by clap.
+    cargo_bin_cmd!().args(["encrypt", "--nope"]).assert().code(2);
+    cargo_bin_cmd!().arg("encrypt").arg(dir.path()).assert().code(2);
+
+    // Missing keys
+    cargo_bin_cmd!()
+        .arg("decrypt")
+        .arg(&file)
+        .arg("--keydir")
+        .arg(dir.path())
+        .assert()
+        .code(3)
+        .stderr(predicates::str::contains("missing private key"));
+
+    // Decryption failures
+    cargo_bin_cmd!()
+        .arg("decrypt")
+        .arg(&file)
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .code(4);
+    cargo_bin_cmd!()
+        .arg("verify")
+        .arg(&file)
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .code(4);
+
+    // Validation failures
+    fs::write(
+        dir.path().join("plain.ejson"),
+        json!({ "password": "hunter2" }).to_string(),
+    )?;
+    cargo_bin_cmd!().arg("check").arg(dir.path()).assert().code(5);
+
+    Ok(())
+}
+
+#[test]
+fn errors_json() -> Result<()> {
+    let dir = assert_fs::TempDir::new()?;
+    let file = dir.path().join("secrets.ejson");
+    fs::write(
+        &file,
+        json!({ "_public_key": PUB_KEY, "user": ENCRYPTED, "database": { "password": OTHER_ENCRYPTED } }).to_string(),
+    )?;
+
+    let output = cargo_bin_cmd!()
+        .args(["--output", "json", "encrypt", "--nope"])
+        .assert()
+        .code(2)
+        .get_output()
+        .stdout
+        .clone();
+    let report = parse(&output);
+    assert_eq!(false, report["ok"]);
+    assert_eq!("usage", report["errors"][0]["kind"]);
+    assert_eq!(Value::Null, report["errors"][0]["file"]);
+
+    let output = cargo_bin_cmd!()
+        .args(["decrypt", "--output", "json", "--keydir"])
+        .arg(dir.path())
+        .arg(&file)
+        .assert()
+        .code(3)
+        .get_output()
+        .stdout
+        .clone();
+    let report = parse(&output);
+    assert_eq!(3, report["exit_code"]);
+    assert_eq!("missing_key", report["errors"][0]["kind"]);
+    assert_eq!(file.display().to_string(), report["errors"][0]["file"]);
+
+    let output = cargo_bin_cmd!()
+        .args(["verify", "--output", "json", "--key-from-stdin"])
+        .arg(&file)
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .code(4)
+        .get_output()
+        .stdout
+        .clone();
+    let report = parse(&output);
+    let path = file.display().to_string();
+    assert_eq!(1, report["result"]["verified"]);
+    assert_eq!(path, report["errors"][0]["file"]);
+    assert_eq!("database.password", report["errors"][0]["key"]);
+    assert_eq!("decryption", report["errors"][0]["kind"]);
+    assert_eq!("database.password", report["warnings"][0]["key"]);
+    assert!(
+        !String::from_utf8(output)?.contains("\"test\""),
+        "plaintexts are never included"
+    );
+
+    fs::write(
+        &file,
+        json!({ "_public_key": PUB_KEY, "password": "hunter2" }).to_string(),
+    )?;
+    let output = cargo_bin_cmd!()
+        .args(["check", "--output", "json"])
+        .arg(&file)
+        .assert()
+        .code(5)
+        .get_output()
+        .stdout
+        .clone();
+    let report = parse(&output);
+    assert_eq!(path, report["errors"][0]["file"]);
+    assert_eq!("password", report["errors"][0]["key"]);
+    assert_eq!("validation", report["errors"][0]["kind"]);
+    assert_eq!(path, report["result"]["findings"][0]["file"]);
+
+    Ok(())
+}
+
+#[test]
+fn warnings_json() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(file.path(), json!({ "_public_key": PUB_KEY }).to_string())?;
+
+    let output = cargo_bin_cmd!()
+        .args(["env", "--key-from-stdin", "--output", "json"])
+        .arg(file.path())
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stderr("")
+        .get_output()
+        .stdout
+        .clone();
+
+    let report = parse(&output);
+    assert_eq!("environment", report["warnings"][0]["key"]);
+    assert_eq!(
+        "No environment key found. Nothing to export.",
+        report["warnings"][0]["message"]
+    );
+
+    Ok(())
+}
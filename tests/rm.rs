@@ -0,0 +1,43 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+#[test]
+fn rm() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        format!(
+            "{{\n  \"_public_key\": \"{}\",\n  \"database\": {{\n    \"password\": \"{}\",\n    \"user\": \"{}\"\n  }}\n}}\n",
+            PUB_KEY, ENCRYPTED, ENCRYPTED
+        ),
+    )?;
+
+    let rm = |key: &str| {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("rm").arg(file.path()).arg(key);
+        cmd
+    };
+
+    rm("database.password").assert().success();
+    rm("database.password")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("database.password not found"));
+
+    // the file's formatting is kept
+    assert_eq!(
+        format!(
+            "{{\n  \"_public_key\": \"{}\",\n  \"database\": {{\n    \"user\": \"{}\"\n  }}\n}}\n",
+            PUB_KEY, ENCRYPTED
+        ),
+        fs::read_to_string(file.path())?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+
+#[test]
+fn set() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        format!(
+            "{{\n    \"_public_key\": \"{}\",\n    \"plain\": \"untouched\",\n    \"database\": {{}}\n}}\n",
+            PUB_KEY
+        ),
+    )?;
+
+    let set = |key: &str, value: &str, args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!();
+        cmd.arg("set").arg(file.path()).arg(key).args(args).write_stdin(value);
+        cmd
+    };
+
+    set("database.password", "hunter2\n", &[]).assert().success();
+    set("tls.[tls.crt]", "cert", &["--create"]).assert().success();
+    set(
+        "settings",
+        r#"{"port": 5432, "token": "abc", "_comment": "plain"}"#,
+        &["--json"],
+    )
+    .assert()
+    .success();
+    set("missing.key", "value", &[])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("missing not found"));
+
+    let contents = fs::read_to_string(file.path())?;
+    let json: serde_json::Value = serde_json::from_str(&contents)?;
+
+    // the file's formatting is kept and only the new values are encrypted
+    assert!(contents.starts_with("{\n    \"_public_key\""));
+    assert_eq!("untouched", json["plain"]);
+    assert_eq!(5432, json["settings"]["port"]);
+    assert_eq!("plain", json["settings"]["_comment"]);
+    assert!(json["settings"]["token"].as_str().unwrap().starts_with("EJ[1:"));
+    assert!(json["tls"]["tls.crt"].as_str().unwrap().starts_with("EJ[1:"));
+
+    cargo_bin_cmd!()
+        .arg("get")
+        .arg(file.path())
+        .arg("database.password")
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout("hunter2\n");
+
+    Ok(())
+}
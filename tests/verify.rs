@@ -0,0 +1,76 @@
+use std::fs;
+
+use anyhow::Result;
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::PredicateBooleanExt;
+
+const PUB_KEY: &str = "b595226c62427adbfc4a809cd7577488a6d402b2f930e1d603164ae3191a616e";
+const PRIV_KEY: &str = "88649a9e83f8f1984ad35ac8e8e86529aab518572c0341f46d1e0bc97f676f2b";
+const ENCRYPTED: &str =
+    "EJ[1:t33Bwgtq7Zghz1P0D+8ZMiSypiQye4q9DWLuxaOrLEU=:XJHmDeBhyT9aLjbuzuHyhQc4kCHki9A9:JNCzvxQwWDmOmtE0AQO/y2RSV2Y=]";
+
+// Encrypted to the key in examples/data rather than PUB_KEY.
+const OTHER_ENCRYPTED: &str =
+    "EJ[1:kzoS8R+Pg09zJm20V4NgUkzaYW1qIkeZjCUZWNzhL3k=:tLyPsfYvMKrHklVhsf6PmdUTD4xSb/Ny:xRas2Ig1wqN7BtZOXkgqFBPR/g==]";
+
+#[test]
+fn verify() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({ "_public_key": PUB_KEY, "user": ENCRYPTED, "database": { "password": ENCRYPTED } })
+            .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("verify")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Verified 2 value(s)"));
+
+    Ok(())
+}
+
+#[test]
+fn verify_failures() -> Result<()> {
+    let file = assert_fs::NamedTempFile::new("secrets.ejson")?;
+    fs::write(
+        file.path(),
+        serde_json::json!({
+            "_public_key": PUB_KEY,
+            "user": ENCRYPTED,
+            "database": { "password": OTHER_ENCRYPTED, "host": ENCRYPTED }
+        })
+        .to_string(),
+    )?;
+
+    cargo_bin_cmd!()
+        .arg("verify")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("error: database.password: failed to decrypt"))
+        .stdout(predicates::str::contains(
+            "warning: database.password: encrypted with a different ephemeral key",
+        ))
+        .stdout(predicates::str::contains("Verified 2 value(s)"))
+        .stdout(predicates::str::contains("test").not())
+        .stderr(predicates::str::contains("could not be decrypted"));
+
+    // decrypt reports the path of the value that failed
+    cargo_bin_cmd!()
+        .arg("decrypt")
+        .arg(file.path())
+        .arg("--key-from-stdin")
+        .write_stdin(PRIV_KEY)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("processing database.password"));
+
+    Ok(())
+}